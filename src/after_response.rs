@@ -0,0 +1,106 @@
+use std::fmt::{self, Formatter};
+use std::future::Future;
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_std::future;
+use tracing::error;
+
+use crate::hooks::BoxFuture;
+
+/// A queue of tasks to run after an invocation's response has been sent, before the next invocation is polled.
+///
+/// Lambda freezes the execution environment once the listener polls for the next invocation, so tasks spawned
+/// with `async_std::task::spawn` during a request may stall until the next invocation, or never run at all.
+/// Tasks queued here are run in order once the response has been posted, and are cancelled if they are still
+/// running near the invocation deadline. Failures are only logged.
+///
+/// Available to handlers via [`LambdaRequestExt::after_response`](crate::LambdaRequestExt::after_response).
+///
+/// ### Example
+/// ```no_run
+/// use tide_lambda_listener::{LambdaListener, LambdaRequestExt};
+///
+/// #[async_std::main]
+/// async fn main() -> tide::http::Result<()> {
+///     let mut server = tide::new();
+///     server.at("/").post(|req: tide::Request<()>| async move {
+///         if let Some(after_response) = req.after_response() {
+///             after_response.push(async {
+///                 // e.g. send an audit event, without delaying the response
+///                 Ok(())
+///             });
+///         }
+///         Ok("Accepted")
+///     });
+///
+///     server.listen(LambdaListener::new()).await?;
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone, Default)]
+pub struct AfterResponse {
+    tasks: Arc<Mutex<Vec<BoxFuture<'static, http_types::Result<()>>>>>,
+}
+
+impl AfterResponse {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue a task to run after the response has been sent.
+    pub fn push<F>(&self, task: F)
+    where
+        F: Future<Output = http_types::Result<()>> + Send + 'static,
+    {
+        self.lock().push(Box::pin(task));
+    }
+
+    /// How many tasks are queued.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Whether no tasks are queued.
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Run the queued tasks in order, including any they queue themselves, cancelling them after `budget`.
+    pub(crate) async fn drain(&self, budget: Duration) {
+        let run = async {
+            loop {
+                let tasks = mem::take(&mut *self.lock());
+                if tasks.is_empty() {
+                    break;
+                }
+                for task in tasks {
+                    if let Err(err) = task.await {
+                        error!("After-response task failed: {}", err);
+                    }
+                }
+            }
+        };
+
+        if future::timeout(budget, run).await.is_err() {
+            error!("After-response tasks did not finish within {:?}", budget);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<BoxFuture<'static, http_types::Result<()>>>> {
+        // A panicking task cannot leave the queue itself in an inconsistent state.
+        self.tasks
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl fmt::Debug for AfterResponse {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AfterResponse")
+            .field("tasks", &self.len())
+            .finish()
+    }
+}
@@ -0,0 +1,278 @@
+use std::convert::TryInto;
+use std::env;
+use std::fmt::{self, Display, Formatter};
+use std::marker::PhantomData;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use http_client::HttpClient;
+use http_types::{url, Url};
+use lambda_runtime::Config;
+use surf::Client;
+
+use crate::shutdown::Shutdown;
+use crate::sources::Routes;
+use crate::{LambdaListener, DEFAULT_DEADLINE_MARGIN, DEFAULT_SHUTDOWN_TIMEOUT};
+
+/// A builder for a [`LambdaListener`](crate::LambdaListener).
+///
+/// By default the Lambda [`Config`](lambda_runtime::Config) is read from the environment,
+/// and a new HTTP/1.1 client without a timeout is used to connect to the Runtime API.
+///
+/// ### Example
+/// ```no_run
+/// use std::time::Duration;
+/// use tide_lambda_listener::LambdaListener;
+///
+/// #[async_std::main]
+/// async fn main() -> tide::http::Result<()> {
+///     let mut server = tide::new();
+///
+///     let listener = LambdaListener::builder()
+///         .endpoint("127.0.0.1:9001")
+///         .timeout(Duration::from_secs(60))
+///         .build()?;
+///     server.listen(listener).await?;
+///
+///     Ok(())
+/// }
+/// ```
+pub struct LambdaListenerBuilder<State> {
+    config: Option<Config>,
+    endpoint: Option<String>,
+    client: Option<Client>,
+    http_client: Option<Box<dyn HttpClient>>,
+    timeout: Option<Duration>,
+    _state: PhantomData<fn() -> State>,
+}
+
+impl<State> LambdaListenerBuilder<State> {
+    /// Create a new `LambdaListenerBuilder`.
+    pub fn new() -> Self {
+        Self {
+            config: None,
+            endpoint: None,
+            client: None,
+            http_client: None,
+            timeout: None,
+            _state: PhantomData,
+        }
+    }
+
+    /// Use this Lambda configuration instead of reading it from the environment.
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Connect to this Runtime API endpoint instead of the one from the Lambda configuration.
+    ///
+    /// This may be a `host:port` pair, as in `AWS_LAMBDA_RUNTIME_API`, or a full `http://` URL.
+    pub fn endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = Some(endpoint.into());
+        self
+    }
+
+    /// Use this surf `Client` to connect to the Runtime API.
+    ///
+    /// The client's base URL is not used. This takes precedence over [`http_client`](Self::http_client) and [`timeout`](Self::timeout).
+    pub fn client(mut self, client: Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Use this `HttpClient` backend to connect to the Runtime API.
+    ///
+    /// This takes precedence over [`timeout`](Self::timeout).
+    pub fn http_client(mut self, http_client: impl HttpClient) -> Self {
+        self.http_client = Some(Box::new(http_client));
+        self
+    }
+
+    /// Set a timeout for requests to the Runtime API.
+    ///
+    /// Defaults to no timeout. Note that `invocation/next` blocks until the next event arrives,
+    /// which may be much longer than any reasonable request timeout.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Build the `LambdaListener`.
+    pub fn build(self) -> Result<LambdaListener<State>, BuildError> {
+        let config = match self.config {
+            Some(config) => config,
+            None => config_from_env()?,
+        };
+
+        let endpoint = self.endpoint.as_deref().unwrap_or(&config.endpoint);
+        let runtime_api = endpoint_url(endpoint)?;
+
+        // Only a client built here can be rebuilt after a thaw.
+        let client_config = match (&self.client, &self.http_client) {
+            (None, None) => Some(http_client::Config::new().set_timeout(self.timeout)),
+            _ => None,
+        };
+        let client = runtime_api_client(self.client, self.http_client, self.timeout);
+
+        Ok(LambdaListener {
+            client: Mutex::new(client),
+            client_config,
+            config,
+            runtime_api,
+            server: None,
+            info: None,
+            binary_media_types: Vec::new(),
+            streaming: false,
+            deadline_margin: DEFAULT_DEADLINE_MARGIN,
+            timeout_response: false,
+            init_hooks: Vec::new(),
+            metrics_namespace: None,
+            shutdown_hooks: Vec::new(),
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+            shutdown: Shutdown::new(),
+            thaw_hooks: Vec::new(),
+            routes: Routes::default(),
+        })
+    }
+}
+
+impl<State> Default for LambdaListenerBuilder<State> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<State> fmt::Debug for LambdaListenerBuilder<State> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LambdaListenerBuilder")
+            .field("config", &self.config)
+            .field("endpoint", &self.endpoint)
+            .field("client", &self.client)
+            .field("http_client", &self.http_client)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+/// Errors which can occur while building a [`LambdaListener`](crate::LambdaListener),
+/// or registering an [`Extension`](crate::extension::Extension).
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum BuildError {
+    /// A required Lambda environment variable was not set.
+    MissingEnv(&'static str),
+    /// A Lambda environment variable was set, but could not be parsed.
+    InvalidEnv(&'static str, String),
+    /// The Runtime API endpoint is not a valid URL.
+    InvalidEndpoint(String, url::ParseError),
+}
+
+impl Display for BuildError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::MissingEnv(var) => write!(f, "Missing `{}` environment variable", var),
+            BuildError::InvalidEnv(var, value) => {
+                write!(f, "Invalid `{}` environment variable: {:?}", var, value)
+            }
+            BuildError::InvalidEndpoint(endpoint, err) => {
+                write!(f, "Invalid Runtime API endpoint {:?}: {}", endpoint, err)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BuildError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BuildError::InvalidEndpoint(_, err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// The surf client for the Runtime API, from the client options of a builder.
+pub(crate) fn runtime_api_client(
+    client: Option<Client>,
+    http_client: Option<Box<dyn HttpClient>>,
+    timeout: Option<Duration>,
+) -> Client {
+    match (client, http_client) {
+        (Some(client), _) => client,
+        (None, Some(http_client)) => Client::with_http_client(http_client),
+        (None, None) => default_client(http_client::Config::new().set_timeout(timeout)),
+    }
+}
+
+/// A new HTTP/1.1 surf client, with its own connection pool.
+pub(crate) fn default_client(config: http_client::Config) -> Client {
+    let http_client: http_client::h1::H1Client =
+        config.try_into().unwrap_or_else(|never| match never {});
+    Client::with_http_client(http_client)
+}
+
+/// Like `Config::from_env`, but without panicking.
+fn config_from_env() -> Result<Config, BuildError> {
+    fn var(name: &'static str) -> Result<String, BuildError> {
+        env::var(name).map_err(|_| BuildError::MissingEnv(name))
+    }
+
+    let memory = var("AWS_LAMBDA_FUNCTION_MEMORY_SIZE")?;
+
+    Ok(Config {
+        endpoint: var("AWS_LAMBDA_RUNTIME_API")?,
+        function_name: var("AWS_LAMBDA_FUNCTION_NAME")?,
+        memory: memory
+            .parse()
+            .map_err(|_| BuildError::InvalidEnv("AWS_LAMBDA_FUNCTION_MEMORY_SIZE", memory))?,
+        version: var("AWS_LAMBDA_FUNCTION_VERSION")?,
+        log_stream: var("AWS_LAMBDA_LOG_STREAM_NAME")?,
+        log_group: var("AWS_LAMBDA_LOG_GROUP_NAME")?,
+    })
+}
+
+/// Normalize a Runtime API endpoint into a base URL which Runtime API paths can be joined onto.
+pub(crate) fn endpoint_url(endpoint: &str) -> Result<Url, BuildError> {
+    let mut url = if endpoint.starts_with("http://") || endpoint.starts_with("https://") {
+        endpoint.to_owned()
+    } else {
+        format!("http://{}", endpoint)
+    };
+    if !url.ends_with('/') {
+        url.push('/');
+    }
+
+    url.parse()
+        .map_err(|err| BuildError::InvalidEndpoint(endpoint.to_owned(), err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn endpoint_url_adds_a_scheme_and_trailing_slash() {
+        assert_eq!(
+            endpoint_url("127.0.0.1:9001").unwrap().as_str(),
+            "http://127.0.0.1:9001/"
+        );
+        assert_eq!(
+            endpoint_url("https://runtime.example.com/prefix")
+                .unwrap()
+                .as_str(),
+            "https://runtime.example.com/prefix/"
+        );
+        assert_eq!(
+            endpoint_url("http://localhost:9001/").unwrap().as_str(),
+            "http://localhost:9001/"
+        );
+    }
+
+    #[test]
+    fn endpoint_url_rejects_invalid_endpoints() {
+        assert!(matches!(
+            endpoint_url("not a host:port"),
+            Err(BuildError::InvalidEndpoint(endpoint, _)) if endpoint == "not a host:port"
+        ));
+    }
+}
@@ -0,0 +1,117 @@
+use std::fs;
+use std::time::Duration;
+
+/// Linux reports process start times in `USER_HZ` clock ticks, which is fixed at 100 for userspace.
+const CLOCK_TICKS_PER_SEC: f64 = 100.0;
+
+/// Whether an invocation is the first one handled by this execution environment.
+///
+/// Inserted as a request extension for every invocation, and available via
+/// [`LambdaRequestExt::cold_start`](crate::LambdaRequestExt::cold_start).
+///
+/// ### Example
+/// ```no_run
+/// use tide_lambda_listener::{LambdaListener, LambdaRequestExt};
+///
+/// #[async_std::main]
+/// async fn main() -> tide::http::Result<()> {
+///     let mut server = tide::new();
+///     server.at("/").get(|req: tide::Request<()>| async move {
+///         match req.cold_start().and_then(|cold_start| cold_start.init_duration()) {
+///             Some(init_duration) => Ok(format!("Cold start after {:?}", init_duration)),
+///             None => Ok("Warm start".to_owned()),
+///         }
+///     });
+///
+///     server.listen(LambdaListener::new()).await?;
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColdStart {
+    cold: bool,
+    init_duration: Option<Duration>,
+}
+
+impl ColdStart {
+    /// The first invocation, `init_duration` after the process started.
+    pub(crate) fn cold(init_duration: Option<Duration>) -> Self {
+        Self {
+            cold: true,
+            init_duration,
+        }
+    }
+
+    /// Any invocation after the first.
+    pub(crate) fn warm() -> Self {
+        Self {
+            cold: false,
+            init_duration: None,
+        }
+    }
+
+    /// Whether this is the first invocation after the process started.
+    pub fn is_cold(&self) -> bool {
+        self.cold
+    }
+
+    /// For the first invocation, the time from process start to polling the Runtime API's `invocation/next`.
+    ///
+    /// This covers everything done before the listener starts, such as building the Tide server and its state,
+    /// as well as [init hooks](crate::LambdaListener::with_init).
+    /// `None` for later invocations, or where the process start time is unavailable.
+    pub fn init_duration(&self) -> Option<Duration> {
+        self.init_duration
+    }
+}
+
+/// How long ago this process started, read from `/proc`.
+pub(crate) fn process_age() -> Option<Duration> {
+    let stat = fs::read_to_string("/proc/self/stat").ok()?;
+    let uptime = fs::read_to_string("/proc/uptime").ok()?;
+    age_from_proc(&stat, &uptime)
+}
+
+/// The age of a process from its `/proc/[pid]/stat`, and the system's `/proc/uptime`.
+fn age_from_proc(stat: &str, uptime: &str) -> Option<Duration> {
+    // The command name may contain spaces and parentheses, so fields are counted from its closing parenthesis.
+    // `starttime` is the 22nd field, and the 20th after the command name.
+    let start_ticks: f64 = stat[stat.rfind(')')? + 1..]
+        .split_whitespace()
+        .nth(19)?
+        .parse()
+        .ok()?;
+    let uptime: f64 = uptime.split_whitespace().next()?.parse().ok()?;
+
+    let age = uptime - start_ticks / CLOCK_TICKS_PER_SEC;
+    if age >= 0.0 {
+        Some(Duration::from_secs_f64(age))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STAT: &str = "1234 (my (odd) cmd) S 1 1234 1234 0 -1 4194560 100 0 0 0 1 2 0 0 20 0 1 0 5000 12345678 100";
+
+    #[test]
+    fn age_from_proc_counts_fields_after_the_command_name() {
+        assert_eq!(
+            age_from_proc(STAT, "62.50 100.00\n"),
+            Some(Duration::from_millis(12_500))
+        );
+    }
+
+    #[test]
+    fn age_from_proc_rejects_malformed_input() {
+        assert_eq!(age_from_proc("1234 (cmd) S 1", "62.50 100.00"), None);
+        assert_eq!(age_from_proc("no command name", "62.50 100.00"), None);
+        assert_eq!(age_from_proc(STAT, "uptime"), None);
+        // Started after the uptime was read.
+        assert_eq!(age_from_proc(STAT, "40.00 100.00"), None);
+    }
+}
@@ -0,0 +1,62 @@
+use std::fmt::{self, Display, Formatter};
+
+use lambda_runtime::Diagnostic;
+
+/// Failures which can occur during a single turn of the runtime loop.
+#[derive(Debug)]
+pub(crate) enum Error {
+    /// A failure scoped to a single invocation.
+    ///
+    /// These are reported to `/runtime/invocation/{id}/error` and the runtime loop carries on.
+    Invocation(Diagnostic),
+    /// A failure talking to the Lambda Runtime API itself.
+    ///
+    /// These terminate the runtime loop.
+    Runtime(http_types::Error),
+}
+
+impl Error {
+    /// Create an invocation-scoped error with an explicit Lambda `errorType`.
+    pub(crate) fn invocation(error_type: impl Into<String>, message: impl Display) -> Self {
+        Error::Invocation(Diagnostic {
+            error_type: error_type.into(),
+            error_message: format!("{}", message),
+        })
+    }
+
+    /// Create a runtime-scoped error, which will stop the listener.
+    pub(crate) fn runtime(err: impl Into<http_types::Error>) -> Self {
+        Error::Runtime(err.into())
+    }
+}
+
+// Most fallible steps of an invocation are invocation-scoped, so `?` defaults to that.
+// Runtime API transport calls must opt into `Error::runtime` explicitly.
+impl From<http_types::Error> for Error {
+    fn from(err: http_types::Error) -> Self {
+        Error::Invocation(diagnostic(&err))
+    }
+}
+
+/// Describe an error for the Runtime API, using the underlying error's type as the `errorType`.
+pub(crate) fn diagnostic(err: &http_types::Error) -> Diagnostic {
+    Diagnostic {
+        error_type: err.type_name().unwrap_or("http_types::Error").to_owned(),
+        error_message: format!("{}", err),
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Invocation(diagnostic) => write!(
+                f,
+                "Invocation error ({}): {}",
+                diagnostic.error_type, diagnostic.error_message
+            ),
+            Error::Runtime(err) => write!(f, "Runtime API error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
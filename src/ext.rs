@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use lambda_http::request::RequestContext;
+use lambda_http::Context;
+use serde_json::{Map, Value};
+
+use crate::sources::{
+    DynamoDbRecord, EventBridgeEvent, KinesisRecord, S3Notification, SnsMessage, SqsMessage,
+};
+use crate::{AfterResponse, ColdStart};
+
+/// Where the Lambda HTTP event for a request originated from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RequestOrigin {
+    /// An API Gateway HTTP API (payload format 2.0), or a Lambda Function URL.
+    ApiGatewayV2,
+    /// An API Gateway REST API (payload format 1.0).
+    ApiGateway,
+    /// An Application Load Balancer.
+    Alb,
+}
+
+impl From<&lambda_http::request::RequestOrigin> for RequestOrigin {
+    fn from(origin: &lambda_http::request::RequestOrigin) -> Self {
+        match origin {
+            lambda_http::request::RequestOrigin::ApiGatewayV2 => RequestOrigin::ApiGatewayV2,
+            lambda_http::request::RequestOrigin::ApiGateway => RequestOrigin::ApiGateway,
+            lambda_http::request::RequestOrigin::Alb => RequestOrigin::Alb,
+        }
+    }
+}
+
+/// Lambda invocation data for Tide requests handled by a [`LambdaListener`](crate::LambdaListener).
+///
+/// All methods return `None` if the request was not dispatched from a Lambda invocation.
+///
+/// ### Example
+/// ```no_run
+/// use tide_lambda_listener::{LambdaListener, LambdaRequestExt};
+///
+/// #[async_std::main]
+/// async fn main() -> tide::http::Result<()> {
+///     let mut server = tide::new();
+///     server.at("/").get(|req: tide::Request<()>| async move {
+///         let request_id = req.request_id().unwrap_or("unknown");
+///         let subject = req
+///             .jwt_claims()
+///             .and_then(|claims| claims.get("sub"))
+///             .and_then(|sub| sub.as_str())
+///             .unwrap_or("anonymous");
+///         Ok(format!("Handling {} for {}", request_id, subject))
+///     });
+///
+///     server.listen(LambdaListener::new()).await?;
+///
+///     Ok(())
+/// }
+/// ```
+pub trait LambdaRequestExt {
+    /// The full Lambda invocation context.
+    fn lambda_context(&self) -> Option<&Context>;
+
+    /// The AWS request id of the invocation.
+    fn request_id(&self) -> Option<&str> {
+        self.lambda_context().map(|ctx| ctx.request_id.as_str())
+    }
+
+    /// When the invocation will time out.
+    fn deadline(&self) -> Option<SystemTime> {
+        self.lambda_context()
+            .map(|ctx| UNIX_EPOCH + Duration::from_millis(ctx.deadline))
+    }
+
+    /// How long remains until the invocation times out, or zero if the deadline has passed.
+    fn remaining_time(&self) -> Option<Duration> {
+        self.lambda_context().map(remaining_time)
+    }
+
+    /// The ARN of the Lambda function, version, or alias which was invoked.
+    fn invoked_function_arn(&self) -> Option<&str> {
+        self.lambda_context()
+            .map(|ctx| ctx.invoked_function_arn.as_str())
+    }
+
+    /// The X-Ray tracing header of the invocation, if tracing is enabled.
+    fn xray_trace_id(&self) -> Option<&str> {
+        self.lambda_context()
+            .map(|ctx| ctx.xray_trace_id.as_str())
+            .filter(|trace_id| !trace_id.is_empty())
+    }
+
+    /// Whether this is the first invocation of the execution environment, and how long initialization took.
+    fn cold_start(&self) -> Option<ColdStart>;
+
+    /// The queue of tasks to run after the response has been sent, before the next invocation is polled.
+    fn after_response(&self) -> Option<&AfterResponse>;
+
+    /// Where the Lambda HTTP event for this request originated from.
+    fn request_origin(&self) -> Option<RequestOrigin>;
+
+    /// The SQS message this request was dispatched for, see [`LambdaListener::with_sqs_route`](crate::LambdaListener::with_sqs_route).
+    fn sqs_message(&self) -> Option<&SqsMessage>;
+
+    /// The EventBridge event this request was dispatched for, see [`LambdaListener::with_eventbridge_route`](crate::LambdaListener::with_eventbridge_route).
+    fn eventbridge_event(&self) -> Option<&EventBridgeEvent>;
+
+    /// The Kinesis record this request was dispatched for, see [`LambdaListener::with_kinesis_route`](crate::LambdaListener::with_kinesis_route).
+    fn kinesis_record(&self) -> Option<&KinesisRecord>;
+
+    /// The DynamoDB Streams record this request was dispatched for, see [`LambdaListener::with_dynamodb_route`](crate::LambdaListener::with_dynamodb_route).
+    fn dynamodb_record(&self) -> Option<&DynamoDbRecord>;
+
+    /// The S3 event notification this request was dispatched for, see [`LambdaListener::with_s3_route`](crate::LambdaListener::with_s3_route).
+    fn s3_notification(&self) -> Option<&S3Notification>;
+
+    /// The SNS message this request was dispatched for, see [`LambdaListener::with_sns_route`](crate::LambdaListener::with_sns_route).
+    fn sns_message(&self) -> Option<&SnsMessage>;
+
+    /// The full API Gateway or ALB request context of the Lambda HTTP event.
+    fn request_context(&self) -> Option<&RequestContext>;
+
+    /// The API Gateway deployment stage, such as `prod` or `$default`.
+    fn stage(&self) -> Option<&str> {
+        match self.request_context()? {
+            RequestContext::ApiGatewayV2(ctx) => Some(&ctx.stage),
+            RequestContext::ApiGateway(ctx) => Some(&ctx.stage),
+            RequestContext::Alb(_) => None,
+        }
+    }
+
+    /// The identifier API Gateway assigned to the API.
+    fn api_id(&self) -> Option<&str> {
+        match self.request_context()? {
+            RequestContext::ApiGatewayV2(ctx) => Some(&ctx.api_id),
+            RequestContext::ApiGateway(ctx) => Some(&ctx.api_id),
+            RequestContext::Alb(_) => None,
+        }
+    }
+
+    /// The source IP address of the connection to API Gateway.
+    fn source_ip(&self) -> Option<&str> {
+        match self.request_context()? {
+            RequestContext::ApiGatewayV2(ctx) => Some(&ctx.http.source_ip),
+            RequestContext::ApiGateway(ctx) => Some(&ctx.identity.source_ip),
+            RequestContext::Alb(_) => None,
+        }
+    }
+
+    /// The `User-Agent` of the API Gateway caller.
+    fn user_agent(&self) -> Option<&str> {
+        match self.request_context()? {
+            RequestContext::ApiGatewayV2(ctx) => Some(&ctx.http.user_agent),
+            RequestContext::ApiGateway(ctx) => ctx.identity.user_agent.as_deref(),
+            RequestContext::Alb(_) => None,
+        }
+    }
+
+    /// The context returned by an API Gateway authorizer.
+    ///
+    /// For HTTP API JWT authorizers, the claims are under `jwt.claims`, see [`jwt_claims`](Self::jwt_claims).
+    fn authorizer(&self) -> Option<&HashMap<String, Value>> {
+        match self.request_context()? {
+            RequestContext::ApiGatewayV2(ctx) => Some(&ctx.authorizer),
+            RequestContext::ApiGateway(ctx) => Some(&ctx.authorizer),
+            RequestContext::Alb(_) => None,
+        }
+    }
+
+    /// The claims validated by an API Gateway HTTP API JWT authorizer.
+    fn jwt_claims(&self) -> Option<&Map<String, Value>> {
+        self.authorizer()?.get("jwt")?.get("claims")?.as_object()
+    }
+
+    /// The full domain name used to invoke an API Gateway HTTP API or Function URL.
+    ///
+    /// Not included in the request context of API Gateway REST API events.
+    fn domain_name(&self) -> Option<&str> {
+        match self.request_context()? {
+            RequestContext::ApiGatewayV2(ctx) => Some(&ctx.domain_name),
+            _ => None,
+        }
+    }
+
+    /// When an API Gateway HTTP API or Function URL received the request, in CLF format.
+    ///
+    /// Not included in the request context of API Gateway REST API events.
+    fn request_time(&self) -> Option<&str> {
+        match self.request_context()? {
+            RequestContext::ApiGatewayV2(ctx) => Some(&ctx.time),
+            _ => None,
+        }
+    }
+}
+
+impl<State> LambdaRequestExt for tide::Request<State> {
+    fn lambda_context(&self) -> Option<&Context> {
+        self.ext::<Context>()
+    }
+
+    fn cold_start(&self) -> Option<ColdStart> {
+        self.ext::<ColdStart>().copied()
+    }
+
+    fn after_response(&self) -> Option<&AfterResponse> {
+        self.ext::<AfterResponse>()
+    }
+
+    fn request_origin(&self) -> Option<RequestOrigin> {
+        self.ext::<RequestOrigin>().copied()
+    }
+
+    fn request_context(&self) -> Option<&RequestContext> {
+        self.ext::<RequestContext>()
+    }
+
+    fn sqs_message(&self) -> Option<&SqsMessage> {
+        self.ext::<SqsMessage>()
+    }
+
+    fn eventbridge_event(&self) -> Option<&EventBridgeEvent> {
+        self.ext::<EventBridgeEvent>()
+    }
+
+    fn kinesis_record(&self) -> Option<&KinesisRecord> {
+        self.ext::<KinesisRecord>()
+    }
+
+    fn dynamodb_record(&self) -> Option<&DynamoDbRecord> {
+        self.ext::<DynamoDbRecord>()
+    }
+
+    fn s3_notification(&self) -> Option<&S3Notification> {
+        self.ext::<S3Notification>()
+    }
+
+    fn sns_message(&self) -> Option<&SnsMessage> {
+        self.ext::<SnsMessage>()
+    }
+}
+
+/// How long remains until the invocation times out, or zero if the deadline has passed.
+pub(crate) fn remaining_time(ctx: &Context) -> Duration {
+    (UNIX_EPOCH + Duration::from_millis(ctx.deadline))
+        .duration_since(SystemTime::now())
+        .unwrap_or(Duration::ZERO)
+}
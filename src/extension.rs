@@ -0,0 +1,407 @@
+//! A client for the Lambda Extensions API, for extensions running alongside a [`LambdaListener`](crate::LambdaListener).
+//!
+//! Extensions register with `/2020-01-01/extension/register` during the init phase, and then poll for `INVOKE`
+//! and `SHUTDOWN` events. Internal extensions, which run in the function's own process, must register before
+//! the listener polls for the first invocation, and can only receive `INVOKE` events.
+//! External extensions, which run as their own process, can also receive `SHUTDOWN` events.
+//!
+//! ### Example
+//! ```no_run
+//! use async_std::task;
+//! use tide_lambda_listener::extension::{Extension, ExtensionEvent};
+//! use tide_lambda_listener::LambdaListener;
+//!
+//! #[async_std::main]
+//! async fn main() -> tide::http::Result<()> {
+//!     let extension = Extension::builder("request-counter").register().await?;
+//!     task::spawn(extension.run(|event| async move {
+//!         if let ExtensionEvent::Invoke(invoke) = event {
+//!             println!("Invoked with {}", invoke.request_id);
+//!         }
+//!         Ok(())
+//!     }));
+//!
+//!     let mut server = tide::new();
+//!     server.listen(LambdaListener::new()).await?;
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use std::env;
+use std::fmt::{self, Formatter};
+use std::future::Future;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use async_std::future;
+use http_client::HttpClient;
+use http_types::{Body, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use surf::Client;
+use tracing::error;
+
+use crate::builder::{endpoint_url, runtime_api_client};
+use crate::error::diagnostic;
+use crate::BuildError;
+
+/// How long Lambda allows extensions to handle a `SHUTDOWN` event.
+pub const SHUTDOWN_BUDGET: Duration = Duration::from_secs(2);
+
+/// The events an extension can register for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[non_exhaustive]
+pub enum EventType {
+    /// An invocation of the function.
+    Invoke,
+    /// The execution environment is shutting down. Only available to external extensions.
+    Shutdown,
+}
+
+/// An event from the Extensions API's `event/next`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "eventType", rename_all = "SCREAMING_SNAKE_CASE")]
+#[non_exhaustive]
+pub enum ExtensionEvent {
+    /// An invocation of the function has started.
+    Invoke(InvokeEvent),
+    /// The execution environment is shutting down.
+    Shutdown(ShutdownEvent),
+}
+
+/// An invocation of the function.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct InvokeEvent {
+    /// The AWS request id of the invocation.
+    pub request_id: String,
+    /// When the invocation will time out, in milliseconds since the Unix epoch.
+    pub deadline_ms: u64,
+    /// The ARN of the Lambda function, version, or alias which was invoked.
+    pub invoked_function_arn: String,
+    /// The X-Ray tracing header of the invocation, if tracing is enabled.
+    #[serde(default)]
+    pub tracing: Option<Tracing>,
+}
+
+impl InvokeEvent {
+    /// When the invocation will time out.
+    pub fn deadline(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(self.deadline_ms)
+    }
+}
+
+/// The tracing header of an invocation.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[non_exhaustive]
+pub struct Tracing {
+    /// The kind of tracing header, `X-Amzn-Trace-Id`.
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// The tracing header.
+    pub value: String,
+}
+
+/// The execution environment is shutting down.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct ShutdownEvent {
+    /// Why the execution environment is shutting down: `spindown`, `timeout`, or `failure`.
+    pub shutdown_reason: String,
+    /// When the execution environment will be stopped, in milliseconds since the Unix epoch.
+    pub deadline_ms: u64,
+}
+
+impl ShutdownEvent {
+    /// When the execution environment will be stopped.
+    pub fn deadline(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(self.deadline_ms)
+    }
+}
+
+/// The function details returned by registration.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Registration {
+    #[serde(default)]
+    function_name: String,
+    #[serde(default)]
+    function_version: String,
+}
+
+/// A builder for registering an [`Extension`].
+///
+/// Like [`LambdaListenerBuilder`](crate::LambdaListenerBuilder), the Runtime API endpoint is read from
+/// `AWS_LAMBDA_RUNTIME_API` by default, and a new HTTP/1.1 client without a timeout is used to connect to it.
+pub struct ExtensionBuilder {
+    name: String,
+    events: Vec<EventType>,
+    endpoint: Option<String>,
+    client: Option<Client>,
+    http_client: Option<Box<dyn HttpClient>>,
+    timeout: Option<Duration>,
+}
+
+impl ExtensionBuilder {
+    /// Create a new `ExtensionBuilder` for an extension registered for `INVOKE` events.
+    ///
+    /// External extensions must be named after their executable in `/opt/extensions`.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            events: vec![EventType::Invoke],
+            endpoint: None,
+            client: None,
+            http_client: None,
+            timeout: None,
+        }
+    }
+
+    /// Register for these events instead of only `INVOKE`.
+    pub fn events(mut self, events: &[EventType]) -> Self {
+        self.events = events.to_vec();
+        self
+    }
+
+    /// Connect to this Runtime API endpoint instead of the one in `AWS_LAMBDA_RUNTIME_API`.
+    ///
+    /// This may be a `host:port` pair, or a full `http://` URL.
+    pub fn endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = Some(endpoint.into());
+        self
+    }
+
+    /// Use this surf `Client` to connect to the Runtime API.
+    ///
+    /// The client's base URL is not used. This takes precedence over [`http_client`](Self::http_client) and [`timeout`](Self::timeout).
+    pub fn client(mut self, client: Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Use this `HttpClient` backend to connect to the Runtime API.
+    ///
+    /// This takes precedence over [`timeout`](Self::timeout).
+    pub fn http_client(mut self, http_client: impl HttpClient) -> Self {
+        self.http_client = Some(Box::new(http_client));
+        self
+    }
+
+    /// Set a timeout for requests to the Runtime API.
+    ///
+    /// Defaults to no timeout. Note that `event/next` blocks until the next event arrives.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Register the extension with the Extensions API.
+    pub async fn register(self) -> http_types::Result<Extension> {
+        let endpoint = match self.endpoint {
+            Some(endpoint) => endpoint,
+            None => env::var("AWS_LAMBDA_RUNTIME_API")
+                .map_err(|_| BuildError::MissingEnv("AWS_LAMBDA_RUNTIME_API"))?,
+        };
+        let runtime_api = endpoint_url(&endpoint)?;
+        let client = runtime_api_client(self.client, self.http_client, self.timeout);
+
+        let mut res = client
+            .post(runtime_api.join("2020-01-01/extension/register")?)
+            .header("Lambda-Extension-Name", self.name.as_str())
+            .body(Body::from_json(&json!({ "events": self.events }))?)
+            .await?;
+        if !res.status().is_success() {
+            return Err(http_types::format_err!(
+                "Failed to register extension {:?}: {} {}",
+                self.name,
+                res.status(),
+                res.body_string().await.unwrap_or_default()
+            ));
+        }
+
+        let id = match res.header("Lambda-Extension-Identifier") {
+            Some(values) => values.as_str().to_owned(),
+            None => {
+                return Err(http_types::format_err!(
+                    "Missing `Lambda-Extension-Identifier` header on registration"
+                ))
+            }
+        };
+        let registration = res.body_json().await.unwrap_or_default();
+
+        Ok(Extension {
+            client,
+            runtime_api,
+            name: self.name,
+            id,
+            registration,
+        })
+    }
+}
+
+impl fmt::Debug for ExtensionBuilder {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExtensionBuilder")
+            .field("name", &self.name)
+            .field("events", &self.events)
+            .field("endpoint", &self.endpoint)
+            .field("client", &self.client)
+            .field("http_client", &self.http_client)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+/// An extension registered with the Lambda Extensions API.
+#[derive(Debug)]
+pub struct Extension {
+    client: Client,
+    runtime_api: Url,
+    name: String,
+    id: String,
+    registration: Registration,
+}
+
+impl Extension {
+    /// Create an [`ExtensionBuilder`] for an extension with this name.
+    pub fn builder(name: impl Into<String>) -> ExtensionBuilder {
+        ExtensionBuilder::new(name)
+    }
+
+    /// The name the extension registered with.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The identifier the Extensions API assigned to the extension.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The name of the function the extension is running alongside.
+    pub fn function_name(&self) -> &str {
+        &self.registration.function_name
+    }
+
+    /// The version of the function the extension is running alongside.
+    pub fn function_version(&self) -> &str {
+        &self.registration.function_version
+    }
+
+    /// Wait for the next event.
+    pub async fn next_event(&self) -> http_types::Result<ExtensionEvent> {
+        let mut res = self
+            .client
+            .get(self.runtime_api.join("2020-01-01/extension/event/next")?)
+            .header("Lambda-Extension-Identifier", self.id.as_str())
+            .await?;
+        if !res.status().is_success() {
+            return Err(http_types::format_err!(
+                "Failed to get the next extension event: {}",
+                res.status()
+            ));
+        }
+
+        res.body_json().await
+    }
+
+    /// Handle events until `SHUTDOWN`, or until `handler` fails.
+    ///
+    /// The handler is given [`SHUTDOWN_BUDGET`], or until the shutdown deadline if that is sooner, to handle `SHUTDOWN`.
+    /// If it fails, the error is reported to the Extensions API's `/extension/exit/error` and returned.
+    pub async fn run<F, Fut>(self, mut handler: F) -> http_types::Result<()>
+    where
+        F: FnMut(ExtensionEvent) -> Fut,
+        Fut: Future<Output = http_types::Result<()>>,
+    {
+        loop {
+            let event = self.next_event().await?;
+
+            let handled = match &event {
+                ExtensionEvent::Shutdown(shutdown) => {
+                    let remaining = shutdown
+                        .deadline()
+                        .duration_since(SystemTime::now())
+                        .unwrap_or(Duration::ZERO);
+                    let budget = remaining.min(SHUTDOWN_BUDGET);
+                    match future::timeout(budget, handler(event.clone())).await {
+                        Ok(handled) => handled,
+                        Err(_) => {
+                            error!(
+                                "Extension {} did not shut down within {:?}",
+                                self.name, budget
+                            );
+                            Ok(())
+                        }
+                    }
+                }
+                _ => handler(event.clone()).await,
+            };
+
+            if let Err(err) = handled {
+                self.report_exit_error(&err).await?;
+                return Err(err);
+            }
+            if let ExtensionEvent::Shutdown(_) = event {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Report a failure during the extension's init to `/extension/init/error`.
+    ///
+    /// The extension should exit after reporting an init error.
+    pub async fn report_init_error(&self, err: &http_types::Error) -> http_types::Result<()> {
+        self.report_error("2020-01-01/extension/init/error", err)
+            .await
+    }
+
+    /// Report a failure which is stopping the extension to `/extension/exit/error`.
+    ///
+    /// The extension should exit after reporting an exit error.
+    pub async fn report_exit_error(&self, err: &http_types::Error) -> http_types::Result<()> {
+        self.report_error("2020-01-01/extension/exit/error", err)
+            .await
+    }
+
+    /// Send a `PUT` to the Runtime API on behalf of this extension, such as a Telemetry API subscription.
+    #[cfg(feature = "telemetry")]
+    pub(crate) async fn put(&self, path: &str, body: Body) -> http_types::Result<()> {
+        let mut res = self
+            .client
+            .put(self.runtime_api.join(path)?)
+            .header("Lambda-Extension-Identifier", self.id.as_str())
+            .body(body)
+            .await?;
+        if !res.status().is_success() {
+            return Err(http_types::format_err!(
+                "Failed to PUT {}: {} {}",
+                path,
+                res.status(),
+                res.body_string().await.unwrap_or_default()
+            ));
+        }
+
+        Ok(())
+    }
+
+    async fn report_error(&self, path: &str, err: &http_types::Error) -> http_types::Result<()> {
+        error!("Extension {} failed: {}", self.name, err); // logs the error in CloudWatch
+
+        let diagnostic = diagnostic(err);
+        self.client
+            .post(self.runtime_api.join(path)?)
+            .header("Lambda-Extension-Identifier", self.id.as_str())
+            .header(
+                "Lambda-Extension-Function-Error-Type",
+                format!("Extension.{}", diagnostic.error_type),
+            )
+            .body(Body::from_json(&diagnostic)?)
+            .await?;
+
+        Ok(())
+    }
+}
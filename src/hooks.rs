@@ -0,0 +1,17 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+pub(crate) type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// An async hook run once during the Lambda init phase, before the first invocation is polled.
+pub(crate) type InitHook =
+    Box<dyn FnOnce() -> BoxFuture<'static, http_types::Result<()>> + Send + Sync>;
+
+/// An async hook run when an invocation arrives after the execution environment was idle, with how long it was idle.
+pub(crate) type ThawHook =
+    Box<dyn Fn(Duration) -> BoxFuture<'static, http_types::Result<()>> + Send + Sync>;
+
+/// An async hook run once when the execution environment shuts down.
+pub(crate) type ShutdownHook =
+    Box<dyn FnOnce() -> BoxFuture<'static, http_types::Result<()>> + Send + Sync>;
@@ -0,0 +1,119 @@
+use std::convert::TryInto;
+
+use http_types::{Body, StatusCode};
+use lambda_http::request::{LambdaRequest, RequestContext, RequestOrigin};
+use lambda_http::response::LambdaResponse;
+use lambda_http::Context;
+use serde_json::Value;
+use tide::Server;
+
+use crate::{ext, response};
+
+/// Invoke a Tide server with a Lambda HTTP event directly, without a Runtime API.
+///
+/// This performs the same translation as [`LambdaListener`](crate::LambdaListener):
+/// the API Gateway or ALB event becomes a Tide request, with `ctx` available via [`LambdaRequestExt`](crate::LambdaRequestExt),
+/// and Tide's response becomes the matching API Gateway or ALB response.
+///
+/// Errors from parsing the event, or returned by the server, are returned as-is.
+///
+/// ### Example
+/// ```
+/// use serde_json::json;
+/// use tide_lambda_listener::Context;
+///
+/// #[async_std::main]
+/// async fn main() -> tide::http::Result<()> {
+///     let mut server = tide::new();
+///     server.at("/hello").get(|_| async { Ok("Hello, Lambda!") });
+///
+///     let event = json!({
+///         "httpMethod": "GET",
+///         "path": "/hello",
+///         "headers": { "host": "example.com" },
+///         "queryStringParameters": null,
+///         "requestContext": { "elb": { "targetGroupArn": "arn" } },
+///         "body": null,
+///         "isBase64Encoded": false
+///     });
+///
+///     let res = tide_lambda_listener::invoke(&server, event, Context::default()).await?;
+///     let res = serde_json::to_value(&res)?;
+///
+///     assert_eq!(res["statusCode"], 200);
+///     assert_eq!(res["body"], "Hello, Lambda!");
+///
+///     Ok(())
+/// }
+/// ```
+pub async fn invoke<State: Clone + Send + Sync + 'static>(
+    server: &Server<State>,
+    event: Value,
+    ctx: Context,
+) -> http_types::Result<LambdaResponse> {
+    let event: LambdaRequest<'_> = serde_json::from_value(event)
+        .map_err(|err| http_types::Error::new(StatusCode::BadRequest, err))?;
+
+    respond(server, event, ctx, &[]).await
+}
+
+/// Translate a Lambda HTTP event into a Tide request, and Tide's response back into a Lambda response.
+pub(crate) async fn respond<State: Clone + Send + Sync + 'static>(
+    server: &Server<State>,
+    event: LambdaRequest<'_>,
+    ctx: Context,
+    binary_media_types: &[String],
+) -> http_types::Result<LambdaResponse> {
+    let (req, request_origin) = into_request(event, ctx)?;
+    let res: http_types::Response = server.respond(req).await?;
+
+    into_lambda_response(res, &request_origin, binary_media_types).await
+}
+
+/// Translate a Lambda HTTP event into a Tide request, with `ctx`, the origin, and the request context as request extensions.
+pub(crate) fn into_request(
+    event: LambdaRequest<'_>,
+    ctx: Context,
+) -> http_types::Result<(http_types::Request, RequestOrigin)> {
+    let request_origin = event.request_origin();
+
+    let hyperium_event: http::Request<lambda_http::Body> = event.into();
+    let (mut parts, body) = hyperium_event.into_parts();
+    // Extensions are not carried over into http-types, so the request context is moved over by hand.
+    let request_context = parts.extensions.remove::<RequestContext>();
+    let body = match body {
+        lambda_http::Body::Empty => Body::empty(),
+        lambda_http::Body::Text(text) => Body::from_string(text),
+        lambda_http::Body::Binary(bytes) => Body::from_bytes(bytes),
+    };
+
+    let mut req: http_types::Request = http::Request::from_parts(parts, body)
+        .try_into()
+        .map_err(|err| http_types::Error::new(StatusCode::BadRequest, err))?;
+
+    req.ext_mut().insert(ctx);
+    req.ext_mut()
+        .insert(ext::RequestOrigin::from(&request_origin));
+    if let Some(request_context) = request_context {
+        req.ext_mut().insert(request_context);
+    }
+
+    Ok((req, request_origin))
+}
+
+/// Translate a Tide response into the Lambda response expected by the event's origin.
+pub(crate) async fn into_lambda_response(
+    mut res: http_types::Response,
+    request_origin: &RequestOrigin,
+    binary_media_types: &[String],
+) -> http_types::Result<LambdaResponse> {
+    let body = res.take_body();
+    let body = response::lambda_body(&res, body, binary_media_types).await?;
+    let res: http::Response<Body> = res.into();
+    let (parts, _) = res.into_parts();
+
+    Ok(LambdaResponse::from_response(
+        request_origin,
+        http::Response::from_parts(parts, body),
+    ))
+}
@@ -23,25 +23,95 @@
     unused_qualifications
 )]
 
-use std::convert::{TryFrom, TryInto};
+use std::convert::TryFrom;
 use std::fmt::{self, Display, Formatter};
-use std::io::{Error as StdError, ErrorKind};
+use std::future::Future;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime};
 
-use async_std::io;
-use http_types::{url, Body};
-use lambda_http::Context;
-use lambda_runtime::Config;
+use async_std::{future, io};
+use http_types::{Body, StatusCode, Url};
+use lambda_runtime::{Config, Diagnostic};
 use surf::Client;
 use tide::listener::{ListenInfo, Listener, ToListener};
 use tide::Server;
-use tracing::{error, trace};
+use tracing::{debug, error, info, info_span, trace, Instrument};
+
+mod after_response;
+mod builder;
+mod cold_start;
+mod error;
+mod ext;
+mod hooks;
+mod invoke;
+mod metrics;
+mod response;
+mod shutdown;
+
+pub mod sources;
+pub mod xray;
+
+pub mod extension;
+
+#[cfg(feature = "logging")]
+pub mod logging;
+
+#[cfg(feature = "opentelemetry")]
+pub mod otel;
+
+#[cfg(feature = "testing")]
+pub mod testing;
+
+#[cfg(feature = "telemetry")]
+pub mod telemetry;
+
+pub use after_response::AfterResponse;
+pub use builder::{BuildError, LambdaListenerBuilder};
+pub use cold_start::ColdStart;
+pub use ext::{LambdaRequestExt, RequestOrigin};
+pub use invoke::invoke;
+pub use lambda_http::request::RequestContext;
+pub use lambda_http::Context;
+pub use shutdown::ShutdownHandle;
+
+use error::Error;
+use hooks::{InitHook, ShutdownHook, ThawHook};
+use metrics::InvocationMetrics;
+use response::LambdaResponseBody;
+use shutdown::Shutdown;
+use sources::Routes;
+
+/// How long before the invocation deadline a handler is cancelled, by default.
+pub const DEFAULT_DEADLINE_MARGIN: Duration = Duration::from_millis(500);
+
+/// How long shutdown hooks may run for, by default.
+///
+/// This is how long Lambda waits after `SIGTERM` when only internal extensions are registered.
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// How long the execution environment must have been idle before the Runtime API client is rebuilt,
+/// as its pooled connections may not have survived being frozen.
+const RECONNECT_AFTER_IDLE: Duration = Duration::from_secs(10);
 
 /// This represents a tide [Listener](tide::listener::Listener) connected to an AWS Lambda execution environment.
 pub struct LambdaListener<State> {
-    client: Client,
+    client: Mutex<Client>,
+    client_config: Option<http_client::Config>,
     config: Config,
+    runtime_api: Url,
     server: Option<Server<State>>,
     info: Option<ListenInfo>,
+    binary_media_types: Vec<String>,
+    streaming: bool,
+    deadline_margin: Duration,
+    timeout_response: bool,
+    init_hooks: Vec<InitHook>,
+    metrics_namespace: Option<String>,
+    shutdown_hooks: Vec<ShutdownHook>,
+    shutdown_timeout: Duration,
+    shutdown: Shutdown,
+    thaw_hooks: Vec<(Duration, ThawHook)>,
+    routes: Routes,
 }
 
 impl<State> LambdaListener<State> {
@@ -60,25 +130,648 @@ impl<State> LambdaListener<State> {
     ///     Ok(())
     /// }
     /// ```
+    ///
+    /// ### Panics
+    /// Panics if the Lambda configuration cannot be read from the environment.
+    /// Use [`LambdaListener::builder`] to handle this as an error instead,
+    /// which can be reported to the Runtime API with [`report_init_error`].
+    // No `Default`, as it would panic outside of Lambda.
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
-        let config = Config::from_env().expect("(Internally asserts)");
-
-        let inner_client: http_client::h1::H1Client = http_client::Config::new()
-            .set_timeout(None)
-            .try_into()
-            .unwrap();
-        let mut client = Client::with_http_client(inner_client);
-        client.set_base_url(
-            format!("http://{}", config.endpoint)
-                .parse()
-                .expect("Must have a valid endpoint URL set in environment; this is a lambda bug"),
-        );
-
-        Self {
-            client,
-            config,
-            server: None,
-            info: None,
+        Self::builder()
+            .build()
+            .expect("Must have a valid Lambda environment; this is a lambda bug")
+    }
+
+    /// Create a [`LambdaListenerBuilder`] to configure how the listener connects to the Runtime API.
+    ///
+    /// ### Example
+    /// ```no_run
+    /// use tide_lambda_listener::LambdaListener;
+    ///
+    /// #[async_std::main]
+    /// async fn main() -> tide::http::Result<()> {
+    ///     let mut server = tide::new();
+    ///
+    ///     let listener = LambdaListener::builder().build()?;
+    ///     server.listen(listener).await?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn builder() -> LambdaListenerBuilder<State> {
+        LambdaListenerBuilder::new()
+    }
+
+    /// Always send responses with any of these media types as binary, base64 encoded, bodies.
+    ///
+    /// Wildcards such as `image/*` or `*/*` are accepted.
+    ///
+    /// Responses with a `Content-Encoding`, or with a media type which is not textual, are always sent as binary.
+    ///
+    /// ### Example
+    /// ```no_run
+    /// use tide_lambda_listener::LambdaListener;
+    ///
+    /// #[async_std::main]
+    /// async fn main() -> tide::http::Result<()> {
+    ///     let mut server = tide::new();
+    ///
+    ///     let listener = LambdaListener::new().with_binary_media_types(&["text/csv", "font/*"]);
+    ///     server.listen(listener).await?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn with_binary_media_types<I>(mut self, media_types: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        self.binary_media_types
+            .extend(media_types.into_iter().map(|m| m.as_ref().to_owned()));
+        self
+    }
+
+    /// Stream responses to the Runtime API as they are produced, rather than buffering them.
+    ///
+    /// This uses Lambda's HTTP integration response streaming, which is supported by Lambda Function URLs
+    /// configured with the `RESPONSE_STREAM` invoke mode. It allows for server-sent events and responses larger than 6 MB.
+    /// Responses to other events, such as from API Gateway or an ALB, are still buffered.
+    /// Bodies are sent as-is, so [binary media types](Self::with_binary_media_types) do not apply.
+    ///
+    /// Once streaming has begun, errors while reading the body can no longer be reported to the Runtime API,
+    /// and are only logged.
+    ///
+    /// ### Example
+    /// ```no_run
+    /// use tide_lambda_listener::LambdaListener;
+    ///
+    /// #[async_std::main]
+    /// async fn main() -> tide::http::Result<()> {
+    ///     let mut server = tide::new();
+    ///
+    ///     let listener = LambdaListener::new().with_response_streaming(true);
+    ///     server.listen(listener).await?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn with_response_streaming(mut self, streaming: bool) -> Self {
+        self.streaming = streaming;
+        self
+    }
+
+    /// Cancel handlers which are still running this long before the invocation deadline.
+    ///
+    /// If Lambda's own timeout is reached, the sandbox is stopped without any response or diagnostic.
+    /// Cancelling the handler a little earlier leaves time to report the timeout to the Runtime API instead.
+    /// Reading a buffered response body counts as part of the handler, streamed bodies are not limited.
+    /// Defaults to [`DEFAULT_DEADLINE_MARGIN`].
+    ///
+    /// ### Example
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use tide_lambda_listener::LambdaListener;
+    ///
+    /// #[async_std::main]
+    /// async fn main() -> tide::http::Result<()> {
+    ///     let mut server = tide::new();
+    ///
+    ///     let listener = LambdaListener::new().with_deadline_margin(Duration::from_secs(1));
+    ///     server.listen(listener).await?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn with_deadline_margin(mut self, margin: Duration) -> Self {
+        self.deadline_margin = margin;
+        self
+    }
+
+    /// Respond with `504 Gateway Timeout` when a handler is cancelled near the invocation deadline.
+    ///
+    /// By default, a `DeadlineExceededError` is reported to the Runtime API instead, which API Gateway
+    /// turns into a `502 Bad Gateway`. See [`with_deadline_margin`](Self::with_deadline_margin).
+    pub fn with_timeout_response(mut self, timeout_response: bool) -> Self {
+        self.timeout_response = timeout_response;
+        self
+    }
+
+    /// Emit CloudWatch metrics for each invocation in the Embedded Metric Format, under `namespace`.
+    ///
+    /// A JSON record is printed to stdout per invocation, which CloudWatch Logs turns into metrics
+    /// without any extra agents or API calls. The record has:
+    ///
+    /// - `Duration`: how long the Tide server took to respond, in milliseconds.
+    /// - `Status2xx`, `Status4xx`, `Status5xx`, etc.: a count for the class of the response status.
+    ///   Events dispatched via routes such as [`with_sqs_route`](Self::with_sqs_route) count as `Status2xx` when every
+    ///   record succeeded.
+    /// - `BatchItemFailures`: for batches of SQS, Kinesis, or DynamoDB records, how many were reported as failed.
+    /// - `ColdStart`: `1` for the first invocation of the execution environment, otherwise `0`.
+    /// - `ResponseSize`: the size of the response body in bytes, when it is known up front.
+    /// - `Errors`: `1` if the invocation was reported to the Runtime API as failed, otherwise `0`.
+    ///
+    /// Metrics are recorded against the `FunctionName` dimension, and against `FunctionName` and `Route`,
+    /// where `Route` is the API Gateway route, such as `GET /items/{id}`. ALB events have no route.
+    ///
+    /// ### Example
+    /// ```no_run
+    /// use tide_lambda_listener::LambdaListener;
+    ///
+    /// #[async_std::main]
+    /// async fn main() -> tide::http::Result<()> {
+    ///     let mut server = tide::new();
+    ///
+    ///     let listener = LambdaListener::new().with_embedded_metrics("MyService");
+    ///     server.listen(listener).await?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn with_embedded_metrics(mut self, namespace: impl Into<String>) -> Self {
+        self.metrics_namespace = Some(namespace.into());
+        self
+    }
+
+    /// Run an async hook during the Lambda init phase, before the first invocation is polled.
+    ///
+    /// If the hook fails, the error is reported to the Runtime API's `/runtime/init/error`,
+    /// so that it shows up in the Lambda console and CloudWatch, and the listener exits.
+    ///
+    /// ### Example
+    /// ```no_run
+    /// use tide_lambda_listener::LambdaListener;
+    ///
+    /// #[async_std::main]
+    /// async fn main() -> tide::http::Result<()> {
+    ///     let mut server = tide::new();
+    ///
+    ///     let listener = LambdaListener::new().with_init(|| async {
+    ///         // e.g. warm up caches or check connectivity
+    ///         Ok(())
+    ///     });
+    ///     server.listen(listener).await?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn with_init<F, Fut>(mut self, init: F) -> Self
+    where
+        F: FnOnce() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = http_types::Result<()>> + Send + 'static,
+    {
+        self.init_hooks.push(Box::new(|| Box::pin(init())));
+        self
+    }
+
+    /// Run an async hook when the execution environment shuts down, such as to flush buffers or close connection pools.
+    ///
+    /// Shutdown happens when the process receives `SIGTERM`, or a [`ShutdownHandle`] is used. The listener stops polling
+    /// for invocations, runs the shutdown hooks in order, and then returns from `listen`.
+    /// Together, the hooks are bounded by [`with_shutdown_timeout`](Self::with_shutdown_timeout).
+    /// Hook failures are only logged.
+    ///
+    /// Lambda only sends `SIGTERM` to the runtime when an extension is registered.
+    ///
+    /// ### Example
+    /// ```no_run
+    /// use tide_lambda_listener::LambdaListener;
+    ///
+    /// #[async_std::main]
+    /// async fn main() -> tide::http::Result<()> {
+    ///     let mut server = tide::new();
+    ///
+    ///     let listener = LambdaListener::new().with_shutdown(|| async {
+    ///         // e.g. flush buffered telemetry
+    ///         Ok(())
+    ///     });
+    ///     server.listen(listener).await?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn with_shutdown<F, Fut>(mut self, shutdown: F) -> Self
+    where
+        F: FnOnce() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = http_types::Result<()>> + Send + 'static,
+    {
+        self.shutdown_hooks.push(Box::new(|| Box::pin(shutdown())));
+        self
+    }
+
+    /// Bound how long [shutdown hooks](Self::with_shutdown) may run for together.
+    ///
+    /// Lambda stops the execution environment 500ms after `SIGTERM` with internal extensions,
+    /// or 2s with external extensions. Defaults to [`DEFAULT_SHUTDOWN_TIMEOUT`].
+    pub fn with_shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.shutdown_timeout = timeout;
+        self
+    }
+
+    /// Run an async hook when an invocation arrives after the execution environment has been idle for at least `idle`.
+    ///
+    /// Lambda freezes the execution environment between invocations, and connections which were pooled
+    /// before a long freeze are often dead after it is thawed. The hook is given how long the environment was idle,
+    /// measured by the wall clock, and runs before the invocation is dispatched to Tide, so it can refresh
+    /// connection pools and other state. This includes the first invocation, which may arrive long after the init phase
+    /// with provisioned concurrency or SnapStart. Hook failures are only logged.
+    ///
+    /// The listener also rebuilds its own Runtime API client after long idle periods,
+    /// unless one was provided with [`LambdaListenerBuilder::client`] or [`LambdaListenerBuilder::http_client`].
+    ///
+    /// ### Example
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use tide_lambda_listener::LambdaListener;
+    ///
+    /// #[async_std::main]
+    /// async fn main() -> tide::http::Result<()> {
+    ///     let mut server = tide::new();
+    ///
+    ///     let listener = LambdaListener::new().with_thaw(Duration::from_secs(60), |idle| async move {
+    ///         println!("Thawed after {:?}, reconnecting", idle);
+    ///         Ok(())
+    ///     });
+    ///     server.listen(listener).await?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn with_thaw<F, Fut>(mut self, idle: Duration, thaw: F) -> Self
+    where
+        F: Fn(Duration) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = http_types::Result<()>> + Send + 'static,
+    {
+        self.thaw_hooks
+            .push((idle, Box::new(move |idle| Box::pin(thaw(idle)))));
+        self
+    }
+
+    /// Dispatch SQS events to Tide, as a `POST` to `route` for each message.
+    ///
+    /// `{queue}` in the route is replaced with the name of the queue, such as `/sqs/{queue}`.
+    /// The message body is the request body, and the message is available via [`LambdaRequestExt::sqs_message`].
+    ///
+    /// Messages are sent in order. Those which fail, or are not handled before the invocation deadline, are reported in
+    /// a `batchItemFailures` response, so that only they are retried. For FIFO queues, the first failure stops the
+    /// batch, and every message after it is reported too, so that messages are not handled out of order.
+    /// This requires `ReportBatchItemFailures` to be enabled on the event source mapping.
+    ///
+    /// ### Example
+    /// ```no_run
+    /// use tide_lambda_listener::{LambdaListener, LambdaRequestExt};
+    ///
+    /// #[async_std::main]
+    /// async fn main() -> tide::http::Result<()> {
+    ///     let mut server = tide::new();
+    ///     server.at("/sqs/orders").post(|mut req: tide::Request<()>| async move {
+    ///         let order: serde_json::Value = req.body_json().await?;
+    ///         println!("Processing order {}", order["id"]);
+    ///         Ok(tide::StatusCode::NoContent)
+    ///     });
+    ///
+    ///     let listener = LambdaListener::new().with_sqs_route("/sqs/{queue}");
+    ///     server.listen(listener).await?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn with_sqs_route(mut self, route: impl Into<String>) -> Self {
+        self.routes.sqs = Some(route.into());
+        self
+    }
+
+    /// Dispatch EventBridge events to Tide, including those from scheduled rules, as a `POST` to `route`.
+    ///
+    /// `{source}` and `{detail-type}` in the route are replaced with those of the event, percent-encoded,
+    /// such as `/events/{source}/{detail-type}`. The event's `detail` is the JSON request body, and the rest of the
+    /// event is available via [`LambdaRequestExt::eventbridge_event`].
+    ///
+    /// The invocation fails unless the response is successful, so that Lambda retries the event.
+    ///
+    /// ### Example
+    /// ```no_run
+    /// use tide_lambda_listener::{LambdaListener, LambdaRequestExt};
+    ///
+    /// #[async_std::main]
+    /// async fn main() -> tide::http::Result<()> {
+    ///     let mut server = tide::new();
+    ///     // Scheduled rules emit events with a `source` of `aws.events`.
+    ///     server.at("/events/aws.events").post(|req: tide::Request<()>| async move {
+    ///         if let Some(event) = req.eventbridge_event() {
+    ///             println!("Rule {:?} fired at {}", event.resources, event.time);
+    ///         }
+    ///         Ok(tide::StatusCode::NoContent)
+    ///     });
+    ///
+    ///     let listener = LambdaListener::new().with_eventbridge_route("/events/{source}");
+    ///     server.listen(listener).await?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn with_eventbridge_route(mut self, route: impl Into<String>) -> Self {
+        self.routes.eventbridge = Some(route.into());
+        self
+    }
+
+    /// Dispatch Kinesis records to Tide, as a `POST` to `route` for each record.
+    ///
+    /// `{stream}` in the route is replaced with the name of the stream, such as `/kinesis/{stream}`.
+    /// The base64 decoded record data is the request body, and the record is available via
+    /// [`LambdaRequestExt::kinesis_record`].
+    ///
+    /// Records are sent in order, stopping at the first which fails or is not handled before the invocation deadline.
+    /// Its sequence number is reported in a `batchItemFailures` response, so that Lambda retries from that record.
+    /// This requires `ReportBatchItemFailures` to be enabled on the event source mapping.
+    ///
+    /// ### Example
+    /// ```no_run
+    /// use tide_lambda_listener::{LambdaListener, LambdaRequestExt};
+    ///
+    /// #[async_std::main]
+    /// async fn main() -> tide::http::Result<()> {
+    ///     let mut server = tide::new();
+    ///     server.at("/kinesis/clicks").post(|mut req: tide::Request<()>| async move {
+    ///         let data = req.body_bytes().await?;
+    ///         let partition_key = req.kinesis_record().map(|record| record.partition_key.clone());
+    ///         println!("{} bytes for {:?}", data.len(), partition_key);
+    ///         Ok(tide::StatusCode::NoContent)
+    ///     });
+    ///
+    ///     let listener = LambdaListener::new().with_kinesis_route("/kinesis/{stream}");
+    ///     server.listen(listener).await?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn with_kinesis_route(mut self, route: impl Into<String>) -> Self {
+        self.routes.kinesis = Some(route.into());
+        self
+    }
+
+    /// Dispatch DynamoDB Streams records to Tide, as a `POST` to `route` for each record.
+    ///
+    /// `{table}` and `{event-name}` in the route are replaced with the name of the table, and `INSERT`, `MODIFY`, or
+    /// `REMOVE`, such as `/dynamodb/{table}/{event-name}`. The request body is a JSON object of the record's `keys`,
+    /// `newImage`, and `oldImage`, converted from DynamoDB attribute values to plain JSON. The record is available via
+    /// [`LambdaRequestExt::dynamodb_record`].
+    ///
+    /// Records are sent in order, stopping at the first which fails or is not handled before the invocation deadline.
+    /// Its sequence number is reported in a `batchItemFailures` response, so that Lambda retries from that record.
+    /// This requires `ReportBatchItemFailures` to be enabled on the event source mapping.
+    ///
+    /// ### Example
+    /// ```no_run
+    /// use tide_lambda_listener::LambdaListener;
+    ///
+    /// #[async_std::main]
+    /// async fn main() -> tide::http::Result<()> {
+    ///     let mut server = tide::new();
+    ///     server.at("/dynamodb/orders/INSERT").post(|mut req: tide::Request<()>| async move {
+    ///         let change: serde_json::Value = req.body_json().await?;
+    ///         println!("New order {}", change["newImage"]["id"]);
+    ///         Ok(tide::StatusCode::NoContent)
+    ///     });
+    ///
+    ///     let listener = LambdaListener::new().with_dynamodb_route("/dynamodb/{table}/{event-name}");
+    ///     server.listen(listener).await?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn with_dynamodb_route(mut self, route: impl Into<String>) -> Self {
+        self.routes.dynamodb = Some(route.into());
+        self
+    }
+
+    /// Dispatch S3 event notifications to Tide, as a `POST` to `route` for each object.
+    ///
+    /// `{bucket}` in the route is replaced with the name of the bucket, such as `/s3/{bucket}`.
+    /// The requests have no body; the object and event are available via [`LambdaRequestExt::s3_notification`],
+    /// with the object key decoded.
+    ///
+    /// Every object is sent, and the invocation fails if any of them failed or were not handled before the
+    /// invocation deadline, so that Lambda retries the event. Handlers should be idempotent, as this retries
+    /// objects which succeeded too.
+    ///
+    /// ### Example
+    /// ```no_run
+    /// use tide_lambda_listener::{LambdaListener, LambdaRequestExt};
+    ///
+    /// #[async_std::main]
+    /// async fn main() -> tide::http::Result<()> {
+    ///     let mut server = tide::new();
+    ///     server.at("/s3/uploads").post(|req: tide::Request<()>| async move {
+    ///         if let Some(notification) = req.s3_notification() {
+    ///             println!("{} {} ({:?} bytes)", notification.event_name, notification.key, notification.size);
+    ///         }
+    ///         Ok(tide::StatusCode::NoContent)
+    ///     });
+    ///
+    ///     let listener = LambdaListener::new().with_s3_route("/s3/{bucket}");
+    ///     server.listen(listener).await?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn with_s3_route(mut self, route: impl Into<String>) -> Self {
+        self.routes.s3 = Some(route.into());
+        self
+    }
+
+    /// Dispatch SNS messages to Tide, as a `POST` to `route` for each message.
+    ///
+    /// `{topic}` in the route is replaced with the name of the topic, such as `/sns/{topic}`.
+    /// The message is the request body, and its message attributes are request headers. The topic, subject, and
+    /// the rest of the message are available via [`LambdaRequestExt::sns_message`].
+    ///
+    /// The invocation fails unless every message is handled successfully, so that SNS retries the event.
+    ///
+    /// ### Example
+    /// ```no_run
+    /// use tide_lambda_listener::{LambdaListener, LambdaRequestExt};
+    ///
+    /// #[async_std::main]
+    /// async fn main() -> tide::http::Result<()> {
+    ///     let mut server = tide::new();
+    ///     server.at("/sns/alerts").post(|mut req: tide::Request<()>| async move {
+    ///         let message = req.body_string().await?;
+    ///         let severity = req.header("severity").map(|values| values.as_str().to_owned());
+    ///         let subject = req.sns_message().and_then(|sns| sns.subject.clone());
+    ///         println!("[{:?}] {:?}: {}", severity, subject, message);
+    ///         Ok(tide::StatusCode::NoContent)
+    ///     });
+    ///
+    ///     let listener = LambdaListener::new().with_sns_route("/sns/{topic}");
+    ///     server.listen(listener).await?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn with_sns_route(mut self, route: impl Into<String>) -> Self {
+        self.routes.sns = Some(route.into());
+        self
+    }
+
+    /// A [`ShutdownHandle`] which asks this listener to shut down.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.handle()
+    }
+
+    /// Report an initialization failure to the Runtime API's `/runtime/init/error`.
+    ///
+    /// This is done automatically for [`with_init`](Self::with_init) hooks, but is useful for failures
+    /// which happen after the listener is created, but before it is listening, such as constructing Tide state.
+    /// The process should exit after reporting an init error.
+    ///
+    /// ### Example
+    /// ```no_run
+    /// use tide_lambda_listener::LambdaListener;
+    ///
+    /// # async fn connect_to_database() -> tide::http::Result<()> { Ok(()) }
+    /// #[async_std::main]
+    /// async fn main() -> tide::http::Result<()> {
+    ///     let listener = LambdaListener::new();
+    ///
+    ///     let state = match connect_to_database().await {
+    ///         Ok(state) => state,
+    ///         Err(err) => {
+    ///             listener.report_init_error(&err).await?;
+    ///             return Err(err);
+    ///         }
+    ///     };
+    ///
+    ///     let server = tide::with_state(state);
+    ///     server.listen(listener).await?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn report_init_error(&self, err: &http_types::Error) -> io::Result<()> {
+        error!("Init error: {}", err); // logs the error in CloudWatch
+
+        post_init_error(&self.client(), &self.runtime_api, &error::diagnostic(err))
+            .await
+            .map_err(io::Error::other)
+    }
+}
+
+/// Report a failure to build a [`LambdaListener`] to the Runtime API's `/runtime/init/error`.
+///
+/// Only `AWS_LAMBDA_RUNTIME_API` is needed, so this can report the rest of the Lambda configuration
+/// being missing from the environment. Once a listener is built, use [`LambdaListener::report_init_error`] instead.
+/// The process should exit after reporting an init error.
+///
+/// ### Example
+/// ```no_run
+/// use tide_lambda_listener::LambdaListener;
+///
+/// #[async_std::main]
+/// async fn main() -> tide::http::Result<()> {
+///     let mut server = tide::new();
+///
+///     let listener = match LambdaListener::builder().build() {
+///         Ok(listener) => listener,
+///         Err(err) => {
+///             tide_lambda_listener::report_init_error(&err).await?;
+///             return Err(err.into());
+///         }
+///     };
+///     server.listen(listener).await?;
+///
+///     Ok(())
+/// }
+/// ```
+pub async fn report_init_error(err: &BuildError) -> io::Result<()> {
+    error!("Init error: {}", err); // logs the error in CloudWatch
+
+    let endpoint = std::env::var("AWS_LAMBDA_RUNTIME_API")
+        .map_err(|_| io::Error::other(BuildError::MissingEnv("AWS_LAMBDA_RUNTIME_API")))?;
+    let runtime_api = builder::endpoint_url(&endpoint).map_err(io::Error::other)?;
+    let client = builder::default_client(http_client::Config::new());
+    let diagnostic = Diagnostic {
+        error_type: "BuildError".to_owned(),
+        error_message: err.to_string(),
+    };
+
+    post_init_error(&client, &runtime_api, &diagnostic)
+        .await
+        .map_err(io::Error::other)
+}
+
+/// Post an initialization failure to `/runtime/init/error`.
+async fn post_init_error(
+    client: &Client,
+    runtime_api: &Url,
+    diagnostic: &Diagnostic,
+) -> Result<(), Error> {
+    let res = client
+        .post(
+            runtime_api
+                .join("2018-06-01/runtime/init/error")
+                .map_err(Error::runtime)?,
+        )
+        .header("lambda-runtime-function-error-type", "unhandled")
+        .body(Body::from_json(diagnostic).map_err(Error::runtime)?)
+        .await
+        .map_err(Error::runtime)?;
+    accepted(res).await
+}
+
+impl<State> LambdaListener<State> {
+    /// Resolve a Runtime API path, such as `2018-06-01/runtime/invocation/next`, against the endpoint.
+    fn runtime_api_url(&self, path: &str) -> Result<Url, Error> {
+        self.runtime_api.join(path).map_err(Error::runtime)
+    }
+
+    /// The Runtime API client.
+    fn client(&self) -> Client {
+        self.lock_client().clone()
+    }
+
+    fn lock_client(&self) -> MutexGuard<'_, Client> {
+        self.client
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Run the thaw hooks for how long the execution environment was idle, and rebuild the Runtime API client if needed.
+    async fn thawed(&self, idle: Duration) {
+        debug!("Thawed after {:?}", idle);
+
+        if idle >= RECONNECT_AFTER_IDLE {
+            if let Some(config) = &self.client_config {
+                *self.lock_client() = builder::default_client(config.clone());
+            }
+        }
+
+        for (after, hook) in &self.thaw_hooks {
+            if idle >= *after {
+                if let Err(err) = hook(idle).await {
+                    error!("Thaw hook failed: {}", err);
+                }
+            }
+        }
+    }
+
+    /// Run the shutdown hooks, within the shutdown timeout.
+    async fn run_shutdown_hooks(&mut self) {
+        let hooks = std::mem::take(&mut self.shutdown_hooks);
+        let run = async {
+            for hook in hooks {
+                if let Err(err) = hook().await {
+                    error!("Shutdown hook failed: {}", err);
+                }
+            }
+        };
+
+        if future::timeout(self.shutdown_timeout, run).await.is_err() {
+            error!(
+                "Shutdown hooks did not finish within {:?}",
+                self.shutdown_timeout
+            );
         }
     }
 }
@@ -91,92 +784,392 @@ impl<State: Clone + Send + Sync + 'static> ToListener<State> for LambdaListener<
     }
 }
 
-// Exists for error conversion
+/// Whether the listener should keep polling for invocations.
+enum Polled {
+    Invocation,
+    Shutdown,
+}
+
+/// Poll the Runtime API for the next invocation and respond to it, unless shutdown is requested while waiting.
+///
+/// Only failures talking to the Runtime API itself are returned,
+/// everything scoped to the invocation is reported to `/runtime/invocation/{id}/error`.
 async fn handle_poll_lambda<State: Clone + Send + Sync + 'static>(
     server: Server<State>,
-    client: &Client,
-    config: &Config,
-) -> http_types::Result<()> {
-    let mut incoming = client.get("2018-06-01/runtime/invocation/next").await?;
-
-    let mut hyperium_headers = http::HeaderMap::new();
-    for (name, values) in incoming.iter() {
-        let name = format!("{}", name).into_bytes();
-        let name = http::header::HeaderName::from_bytes(&name).unwrap();
-
-        for value in values.iter() {
-            let value = format!("{}", value).into_bytes();
-            let value = http::header::HeaderValue::from_bytes(&value).unwrap();
-            hyperium_headers.append(&name, value);
-        }
-    }
+    listener: &LambdaListener<State>,
+    cold_start: bool,
+) -> Result<Polled, Error> {
+    let client = listener.client();
 
-    let ctx: Context =
-        Context::try_from(hyperium_headers).map_err(|e| StdError::new(ErrorKind::Other, e))?;
-    let ctx: Context = ctx.with_config(config);
-    let request_id = ctx.request_id.clone();
+    let cold_start = if cold_start {
+        ColdStart::cold(cold_start::process_age())
+    } else {
+        ColdStart::warm()
+    };
 
-    let event: lambda_http::request::LambdaRequest<'_> = incoming.body_json().await?;
-    let request_origin = event.request_origin();
+    // The wall clock keeps time while the execution environment is frozen, which monotonic clocks may not.
+    let polled_at = SystemTime::now();
+    let next = client.get(listener.runtime_api_url("2018-06-01/runtime/invocation/next")?);
+    let shutdown = async {
+        listener.shutdown.requested().await;
+        None
+    };
+    let mut incoming = match futures_lite::future::or(async { Some(next.await) }, shutdown).await {
+        Some(incoming) => incoming.map_err(Error::runtime)?,
+        None => return Ok(Polled::Shutdown),
+    };
+    let idle = SystemTime::now()
+        .duration_since(polled_at)
+        .unwrap_or(Duration::ZERO);
 
-    let hyperium_event: http::Request<lambda_http::Body> = event.into();
-    let (parts, body) = hyperium_event.into_parts();
-    let body = match body {
-        lambda_http::Body::Empty => Body::empty(),
-        lambda_http::Body::Text(text) => Body::from_string(text),
-        lambda_http::Body::Binary(bytes) => Body::from_bytes(bytes),
+    // Without a request id there is nowhere to report a failure to.
+    let request_id = match incoming.header("lambda-runtime-aws-request-id") {
+        Some(values) => values.as_str().to_owned(),
+        None => {
+            return Err(Error::runtime(http_types::format_err!(
+                "Missing `lambda-runtime-aws-request-id` header on the next invocation"
+            )))
+        }
     };
 
-    let mut req: http_types::Request = http::Request::from_parts(parts, body).try_into()?;
+    let event = incoming.body_bytes().await.map_err(Error::runtime)?;
 
-    req.ext_mut().insert(ctx);
-    let res: http_types::Result<http_types::Response> = server.respond(req).await;
+    // Only present when active tracing is enabled.
+    let trace_id = match incoming.header("lambda-runtime-trace-id") {
+        Some(values) => values.as_str().to_owned(),
+        None => String::new(),
+    };
+    xray::set_current_trace_id(&trace_id);
 
-    match res {
-        Ok(res) => {
-            let res: http::Response<Body> = res.try_into()?;
-            let (parts, body) = res.into_parts();
-            let body = match body.is_empty() {
-                Some(true) => lambda_http::Body::Empty,
-                _ => lambda_http::Body::Text(body.into_string().await?),
-            };
-            let lambda_res = lambda_http::response::LambdaResponse::from_response(
-                &request_origin,
-                http::Response::from_parts(parts, body),
-            );
+    let span = info_span!(
+        "invocation",
+        request_id = %request_id,
+        xray_trace_id = %trace_id,
+        cold_start = cold_start.is_cold(),
+        init_duration_ms = tracing::field::Empty,
+    );
+    if let Some(init_duration) = cold_start.init_duration() {
+        span.record("init_duration_ms", init_duration.as_millis() as u64);
+    }
 
+    let invocation = async {
+        if cold_start.is_cold() {
+            info!("Cold start");
+        }
+        // With provisioned concurrency or SnapStart, even the first invocation may arrive long after init.
+        listener.thawed(idle).await;
+        complete_invocation(server, listener, &incoming, &event, &request_id, cold_start).await
+    };
+    invocation.instrument(span).await?;
+
+    Ok(Polled::Invocation)
+}
+
+/// Respond to a single invocation, and post the result to the Runtime API.
+async fn complete_invocation<State: Clone + Send + Sync + 'static>(
+    server: Server<State>,
+    listener: &LambdaListener<State>,
+    incoming: &surf::Response,
+    event: &[u8],
+    request_id: &str,
+    cold_start: ColdStart,
+) -> Result<(), Error> {
+    let client = listener.client();
+    let mut metrics = InvocationMetrics::new(cold_start.is_cold());
+    let after_response = AfterResponse::new();
+
+    match handle_invocation(
+        server,
+        listener,
+        incoming,
+        event,
+        request_id,
+        Extensions {
+            cold_start,
+            after_response: after_response.clone(),
+        },
+        &mut metrics,
+    )
+    .await
+    {
+        Ok(lambda_res) => {
             trace!("Ok response from handler (run loop)");
 
-            client
-                .post(format!(
-                    "2018-06-01/runtime/invocation/{}/response",
-                    request_id
-                ))
-                .body(Body::from_json(&lambda_res)?)
-                .await?;
-        }
-        Err(err) => {
-            error!("{}", err); // logs the error in CloudWatch
+            let post = client.post(listener.runtime_api_url(&format!(
+                "2018-06-01/runtime/invocation/{}/response",
+                request_id
+            ))?);
+
+            let rejected = match lambda_res {
+                LambdaResponseBody::Buffered(body) => {
+                    let res = post.body(body).await.map_err(Error::runtime)?;
+                    rejection(res).await
+                }
+                LambdaResponseBody::Streaming(body, failure) => {
+                    let posted = post
+                        .header("lambda-runtime-function-response-mode", "streaming")
+                        .content_type(response::HTTP_INTEGRATION_RESPONSE)
+                        .body(body)
+                        .await;
 
-            let diagnostic_res = lambda_runtime::Diagnostic {
-                error_type: type_name_of(&err).to_owned(),
-                error_message: format!("{}", err), // returns the error to the caller via Lambda API
+                    // The response head has already been sent, so a body failure can only be logged.
+                    match (posted, failure.take()) {
+                        (_, Some(message)) => {
+                            error!("Streamed response body failed: {}", message);
+                            None
+                        }
+                        (Err(err), None) => return Err(Error::runtime(err)),
+                        (Ok(res), None) => rejection(res).await,
+                    }
+                }
             };
 
-            client
-                .post(format!(
-                    "2018-06-01/runtime/invocation/{}/error",
-                    request_id
-                ))
-                .header("lambda-runtime-function-error-type", "unhandled")
-                .body(Body::from_json(&diagnostic_res)?)
-                .await?;
+            // Otherwise the invocation would hang until Lambda's timeout.
+            if let Some(diagnostic) = rejected {
+                error!("{}", diagnostic.error_message); // logs the error in CloudWatch
+                metrics.error = true;
+                post_invocation_error(&client, listener, request_id, &diagnostic).await?;
+            }
+        }
+        Err(Error::Invocation(diagnostic)) => {
+            error!("{}", diagnostic.error_message); // logs the error in CloudWatch
+            metrics.error = true;
+            post_invocation_error(&client, listener, request_id, &diagnostic).await?;
+        }
+        Err(err) => return Err(err),
+    }
+
+    if let Some(namespace) = &listener.metrics_namespace {
+        metrics::emit(&metrics.to_emf(namespace, &listener.config.function_name, request_id));
+    }
+
+    // Tasks can only have been queued by a handler, so the context is known to be valid.
+    if !after_response.is_empty() {
+        if let Ok(ctx) = context_from_headers(incoming, request_id) {
+            let budget = ext::remaining_time(&ctx).saturating_sub(listener.deadline_margin);
+            after_response.drain(budget).await;
         }
     }
 
     Ok(())
 }
 
+/// Post an invocation-scoped failure to `/runtime/invocation/{id}/error`.
+async fn post_invocation_error<State>(
+    client: &Client,
+    listener: &LambdaListener<State>,
+    request_id: &str,
+    diagnostic: &Diagnostic,
+) -> Result<(), Error> {
+    let res = client
+        .post(listener.runtime_api_url(&format!(
+            "2018-06-01/runtime/invocation/{}/error",
+            request_id
+        ))?)
+        .header("lambda-runtime-function-error-type", "unhandled")
+        .body(Body::from_json(diagnostic).map_err(Error::runtime)?)
+        .await
+        .map_err(Error::runtime)?;
+    accepted(res).await
+}
+
+/// Check that the Runtime API accepted a post, as surf does not treat error statuses as failures.
+async fn accepted(mut res: surf::Response) -> Result<(), Error> {
+    if res.status().is_success() {
+        return Ok(());
+    }
+    let body = res.body_string().await.unwrap_or_default();
+    Err(Error::runtime(http_types::format_err!(
+        "Runtime API responded {}: {}",
+        res.status(),
+        body
+    )))
+}
+
+/// Why the Runtime API rejected an invocation response, if it did, such as it being over the 6 MB payload limit.
+async fn rejection(mut res: surf::Response) -> Option<Diagnostic> {
+    if res.status().is_success() {
+        return None;
+    }
+    let error_type = match res.status() {
+        StatusCode::PayloadTooLarge => "Runtime.ResponseTooLarge",
+        _ => "Runtime.ResponseRejected",
+    };
+    let body = res.body_string().await.unwrap_or_default();
+    Some(Diagnostic {
+        error_type: error_type.to_owned(),
+        error_message: format!(
+            "Runtime API rejected the response ({}): {}",
+            res.status(),
+            body
+        ),
+    })
+}
+
+/// Per-invocation state handed to Tide as request extensions.
+pub(crate) struct Extensions {
+    cold_start: ColdStart,
+    after_response: AfterResponse,
+}
+
+impl Extensions {
+    /// Insert the extensions into a request for this invocation.
+    pub(crate) fn insert(&self, req: &mut http_types::Request) {
+        req.ext_mut().insert(self.cold_start);
+        req.ext_mut().insert(self.after_response.clone());
+    }
+}
+
+/// Translate a single invocation into a Tide request, and Tide's response back into a Lambda response body.
+async fn handle_invocation<State: Clone + Send + Sync + 'static>(
+    server: Server<State>,
+    listener: &LambdaListener<State>,
+    incoming: &surf::Response,
+    event: &[u8],
+    request_id: &str,
+    extensions: Extensions,
+    metrics: &mut InvocationMetrics,
+) -> Result<LambdaResponseBody, Error> {
+    let ctx = context_from_headers(incoming, request_id)?.with_config(&listener.config);
+    // Leave enough time before Lambda's own timeout to report back to the Runtime API.
+    let budget = ext::remaining_time(&ctx).saturating_sub(listener.deadline_margin);
+
+    if !listener.routes.is_empty() {
+        let event: serde_json::Value = serde_json::from_slice(event)
+            .map_err(|e| Error::invocation("InvalidEventDataError", e))?;
+        let invocation = sources::Invocation {
+            ctx: &ctx,
+            extensions: &extensions,
+            deadline: Instant::now() + budget,
+        };
+
+        let started = Instant::now();
+        if let Some(res) = sources::dispatch(&server, &listener.routes, &event, &invocation).await {
+            metrics.duration = Some(started.elapsed());
+            let res = res?;
+            metrics.batch_item_failures = res
+                .get("batchItemFailures")
+                .and_then(serde_json::Value::as_array)
+                .map(Vec::len);
+            // A batch with failed items is not a success, even though the invocation is.
+            if metrics.batch_item_failures.unwrap_or(0) == 0 {
+                metrics.status = Some(StatusCode::Ok);
+            }
+            return Ok(LambdaResponseBody::Buffered(Body::from_json(&res)?));
+        }
+    }
+
+    let event: lambda_http::request::LambdaRequest<'_> =
+        serde_json::from_slice(event).map_err(|e| Error::invocation("InvalidEventDataError", e))?;
+
+    #[cfg(feature = "opentelemetry")]
+    let xray_trace_id = ctx.xray_trace_id.clone();
+
+    let (mut req, request_origin) = invoke::into_request(event, ctx)?;
+    extensions.insert(&mut req);
+    metrics.set_route(req.ext().get::<RequestContext>());
+    let streaming =
+        listener.streaming && response::is_function_url(req.ext().get::<RequestContext>());
+
+    #[cfg(feature = "opentelemetry")]
+    let (req, otel_cx) = otel::start_invocation(req, &xray_trace_id, request_id);
+
+    // Reading a buffered body can be as slow as the handler itself, such as when proxying, so it shares the budget.
+    let binary_media_types = &listener.binary_media_types;
+    let respond = async {
+        let res: http_types::Response = server.respond(req).await?;
+        let (status, size) = (res.status(), res.len());
+        let body =
+            lambda_response_body(res, streaming, &request_origin, binary_media_types).await?;
+        Ok::<_, http_types::Error>((status, size, body))
+    };
+    let respond = future::timeout(budget, respond);
+    #[cfg(feature = "opentelemetry")]
+    let respond = opentelemetry::trace::FutureExt::with_context(respond, otel_cx.clone());
+
+    let started = Instant::now();
+    let responded = respond.await;
+    metrics.duration = Some(started.elapsed());
+
+    let (status, size, body) = match responded {
+        Ok(Ok(responded)) => responded,
+        Ok(Err(err)) => {
+            #[cfg(feature = "opentelemetry")]
+            otel::fail_invocation(&otel_cx, Some(err.status()), err.to_string());
+            return Err(err.into());
+        }
+        Err(_) => {
+            let message = format!(
+                "Handler did not respond within {:?} of the invocation deadline",
+                listener.deadline_margin
+            );
+            if !listener.timeout_response {
+                #[cfg(feature = "opentelemetry")]
+                otel::fail_invocation(&otel_cx, None, message.clone());
+                return Err(Error::invocation("DeadlineExceededError", message));
+            }
+            error!("{}", message); // logs the error in CloudWatch
+            let res = http_types::Response::new(StatusCode::GatewayTimeout);
+            let (status, size) = (res.status(), res.len());
+            let body =
+                lambda_response_body(res, streaming, &request_origin, binary_media_types).await?;
+            (status, size, body)
+        }
+    };
+
+    metrics.status = Some(status);
+    metrics.response_size = size;
+
+    #[cfg(feature = "opentelemetry")]
+    otel::end_invocation(&otel_cx, status);
+
+    Ok(body)
+}
+
+/// Convert a Tide response into a Lambda response body, either streamed or buffered.
+async fn lambda_response_body(
+    res: http_types::Response,
+    streaming: bool,
+    request_origin: &lambda_http::request::RequestOrigin,
+    binary_media_types: &[String],
+) -> http_types::Result<LambdaResponseBody> {
+    if streaming {
+        let (body, failure) = response::streaming_body(res)?;
+        return Ok(LambdaResponseBody::Streaming(body, failure));
+    }
+
+    let lambda_res = invoke::into_lambda_response(res, request_origin, binary_media_types).await?;
+    Ok(LambdaResponseBody::Buffered(Body::from_json(&lambda_res)?))
+}
+
+/// Build the invocation `Context` from the Runtime API's `invocation/next` headers.
+///
+/// Unlike `Context::try_from(HeaderMap)`, this does not panic on missing or malformed headers.
+fn context_from_headers(incoming: &surf::Response, request_id: &str) -> Result<Context, Error> {
+    let header = |name: &str| -> Result<String, Error> {
+        incoming
+            .header(name)
+            .map(|values| values.as_str().to_owned())
+            .ok_or_else(|| {
+                Error::invocation("InvalidContextError", format!("Missing `{}` header", name))
+            })
+    };
+
+    let mut ctx = Context::default();
+    ctx.request_id = request_id.to_owned();
+    ctx.deadline = header("lambda-runtime-deadline-ms")?.parse().map_err(|e| {
+        Error::invocation(
+            "InvalidContextError",
+            format!("Invalid `lambda-runtime-deadline-ms` header: {}", e),
+        )
+    })?;
+    ctx.invoked_function_arn = header("lambda-runtime-invoked-function-arn")?;
+    // Only present when active tracing is enabled.
+    ctx.xray_trace_id = header("lambda-runtime-trace-id").unwrap_or_default();
+
+    Ok(ctx)
+}
+
 #[tide::utils::async_trait]
 impl<State> Listener<State> for LambdaListener<State>
 where
@@ -186,6 +1179,13 @@ where
         assert!(self.server.is_none(), "`bind` should only be called once");
         self.server = Some(server);
 
+        for init in std::mem::take(&mut self.init_hooks) {
+            if let Err(err) = init().await {
+                self.report_init_error(&err).await?;
+                return Err(io::Error::other(err));
+            }
+        }
+
         Ok(())
     }
 
@@ -195,11 +1195,25 @@ where
             .take()
             .expect("`Listener::bind` must be called before `Listener::accept`");
 
+        if !self.shutdown_hooks.is_empty() {
+            shutdown::on_sigterm(self.shutdown.handle());
+        }
+
+        let mut cold_start = true;
         loop {
-            handle_poll_lambda(server.clone(), &self.client, &self.config)
-                .await
-                .expect("Runtime failure");
+            match handle_poll_lambda(server.clone(), self, cold_start).await {
+                Ok(Polled::Invocation) => cold_start = false,
+                Ok(Polled::Shutdown) => break,
+                Err(err) => {
+                    error!("{}", err);
+                    return Err(io::Error::other(err));
+                }
+            }
         }
+
+        self.run_shutdown_hooks().await;
+
+        Ok(())
     }
 
     fn info(&self) -> Vec<ListenInfo> {
@@ -213,16 +1227,27 @@ where
 impl<State> fmt::Debug for LambdaListener<State> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         f.debug_struct("LambdaListener")
-            .field(&"client", &self.client)
-            .field(&"config", &self.config)
+            .field("client", &self.client())
+            .field("config", &self.config)
+            .field("runtime_api", &self.runtime_api)
             .field(
-                &"server",
+                "server",
                 if self.server.is_some() {
                     &"Some(Server<State>)"
                 } else {
                     &"None"
                 },
             )
+            .field("binary_media_types", &self.binary_media_types)
+            .field("streaming", &self.streaming)
+            .field("deadline_margin", &self.deadline_margin)
+            .field("timeout_response", &self.timeout_response)
+            .field("init_hooks", &self.init_hooks.len())
+            .field("metrics_namespace", &self.metrics_namespace)
+            .field("shutdown_hooks", &self.shutdown_hooks.len())
+            .field("shutdown_timeout", &self.shutdown_timeout)
+            .field("thaw_hooks", &self.thaw_hooks.len())
+            .field("routes", &self.routes)
             .finish()
     }
 }
@@ -248,21 +1273,9 @@ impl<State> Display for LambdaListener<State> {
 }
 
 impl<State> TryFrom<Config> for LambdaListener<State> {
-    type Error = url::ParseError;
+    type Error = BuildError;
 
     fn try_from(config: Config) -> Result<Self, Self::Error> {
-        let mut client = surf::client();
-        client.set_base_url(config.endpoint.parse()?);
-
-        Ok(Self {
-            client,
-            config,
-            server: None,
-            info: None,
-        })
+        Self::builder().config(config).build()
     }
 }
-
-fn type_name_of<T: ?Sized>(_val: &T) -> &'static str {
-    std::any::type_name::<T>()
-}
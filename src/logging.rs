@@ -0,0 +1,247 @@
+//! A tracing subscriber which logs in the format configured for the Lambda function.
+//!
+//! Requires the `logging` cargo feature.
+//!
+//! With Lambda's advanced logging controls, the log format and level are configured on the function and passed
+//! to the runtime as `AWS_LAMBDA_LOG_FORMAT` and `AWS_LAMBDA_LOG_LEVEL`. [`init`] installs a global subscriber which
+//! honors both, and stamps every line logged during an invocation with its `requestId`, like Lambda's native runtimes.
+//!
+//! With the `JSON` format, each line is a JSON object:
+//!
+//! ```text
+//! {"timestamp":"2024-01-01T12:00:00.000Z","level":"INFO","requestId":"8f50...","functionName":"my-function","functionVersion":"$LATEST","message":"Cold start"}
+//! ```
+//!
+//! Other fields of the event are included alongside `message`.
+//! Otherwise, lines are the timestamp, request id, level, and message separated by tabs, with other fields appended.
+//!
+//! ### Example
+//! ```no_run
+//! use tide_lambda_listener::LambdaListener;
+//!
+//! #[async_std::main]
+//! async fn main() -> tide::http::Result<()> {
+//!     tide_lambda_listener::logging::init()?;
+//!
+//!     let mut server = tide::new();
+//!     server.listen(LambdaListener::new()).await?;
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use std::env;
+use std::fmt::{self, Write as _};
+use std::io::{self, Write as _};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::{Map, Value};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::subscriber::SetGlobalDefaultError;
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::{LookupSpan, Registry};
+
+/// The environment variable Lambda uses for the configured log format, `Text` or `JSON`.
+pub const LOG_FORMAT_ENV: &str = "AWS_LAMBDA_LOG_FORMAT";
+
+/// The environment variable Lambda uses for the configured application log level.
+pub const LOG_LEVEL_ENV: &str = "AWS_LAMBDA_LOG_LEVEL";
+
+/// Install a global tracing subscriber configured from `AWS_LAMBDA_LOG_FORMAT` and `AWS_LAMBDA_LOG_LEVEL`.
+///
+/// The format defaults to text, and the level to `INFO`. Lambda's `FATAL` level is treated as `ERROR`.
+///
+/// Fails if a global subscriber has already been installed.
+pub fn init() -> Result<(), SetGlobalDefaultError> {
+    let level = env::var(LOG_LEVEL_ENV)
+        .ok()
+        .and_then(|level| level_filter(&level))
+        .unwrap_or(LevelFilter::INFO);
+
+    let layer = LambdaLogLayer {
+        json: env::var(LOG_FORMAT_ENV).is_ok_and(|format| format.eq_ignore_ascii_case("json")),
+        function_name: env::var("AWS_LAMBDA_FUNCTION_NAME").unwrap_or_default(),
+        function_version: env::var("AWS_LAMBDA_FUNCTION_VERSION").unwrap_or_default(),
+    };
+
+    tracing::subscriber::set_global_default(Registry::default().with(level).with(layer))
+}
+
+fn level_filter(level: &str) -> Option<LevelFilter> {
+    match level.to_ascii_uppercase().as_str() {
+        "TRACE" => Some(LevelFilter::TRACE),
+        "DEBUG" => Some(LevelFilter::DEBUG),
+        "INFO" => Some(LevelFilter::INFO),
+        "WARN" => Some(LevelFilter::WARN),
+        "ERROR" | "FATAL" => Some(LevelFilter::ERROR),
+        _ => None,
+    }
+}
+
+/// Formats events as Lambda log lines.
+#[derive(Debug)]
+struct LambdaLogLayer {
+    json: bool,
+    function_name: String,
+    function_version: String,
+}
+
+/// The request id recorded on a span, such as the listener's `invocation` span.
+#[derive(Debug)]
+struct RequestId(String);
+
+impl<S> Layer<S> for LambdaLogLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut fields = Fields::default();
+        attrs.record(&mut fields);
+
+        if let (Some(Value::String(request_id)), Some(span)) =
+            (fields.0.remove("request_id"), ctx.span(id))
+        {
+            span.extensions_mut().insert(RequestId(request_id));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let request_id = ctx.event_scope(event).and_then(|scope| {
+            scope
+                .into_iter()
+                .find_map(|span| span.extensions().get::<RequestId>().map(|id| id.0.clone()))
+        });
+
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let message = match fields.0.remove("message") {
+            Some(Value::String(message)) => message,
+            Some(message) => message.to_string(),
+            None => String::new(),
+        };
+
+        let line = if self.json {
+            let mut record = Map::new();
+            record.insert("timestamp".to_owned(), timestamp().into());
+            record.insert("level".to_owned(), level(event.metadata().level()).into());
+            if let Some(request_id) = request_id {
+                record.insert("requestId".to_owned(), request_id.into());
+            }
+            record.insert("functionName".to_owned(), self.function_name.clone().into());
+            record.insert(
+                "functionVersion".to_owned(),
+                self.function_version.clone().into(),
+            );
+            record.insert("message".to_owned(), message.into());
+            record.extend(fields.0);
+            Value::Object(record).to_string()
+        } else {
+            let mut line = format!(
+                "{}\t{}\t{}\t{}",
+                timestamp(),
+                request_id.as_deref().unwrap_or("-"),
+                level(event.metadata().level()),
+                message
+            );
+            for (name, value) in fields.0 {
+                let _ = write!(line, " {}={}", name, value);
+            }
+            line
+        };
+
+        let _ = writeln!(io::stdout().lock(), "{}", line);
+    }
+}
+
+/// Lambda's names for log levels.
+fn level(level: &Level) -> &'static str {
+    match *level {
+        Level::TRACE => "TRACE",
+        Level::DEBUG => "DEBUG",
+        Level::INFO => "INFO",
+        Level::WARN => "WARN",
+        Level::ERROR => "ERROR",
+    }
+}
+
+/// Collects the fields of a span or event as JSON values.
+#[derive(Debug, Default)]
+struct Fields(Map<String, Value>);
+
+impl Visit for Fields {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_owned(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_owned(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_owned(), value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_owned(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().to_owned(), format!("{:?}", value).into());
+    }
+}
+
+/// The current time in RFC 3339 format, in UTC with millisecond precision.
+fn timestamp() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = now.as_secs();
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let secs_of_day = secs % 86_400;
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60,
+        now.subsec_millis()
+    )
+}
+
+/// Convert days since the Unix epoch into a `(year, month, day)` date.
+///
+/// See <http://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn civil_from_days_converts_known_dates() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+        assert_eq!(civil_from_days(19_358), (2023, 1, 1));
+        assert_eq!(civil_from_days(19_782), (2024, 2, 29));
+    }
+}
@@ -0,0 +1,110 @@
+//! CloudWatch Embedded Metric Format (EMF) records for invocations.
+//!
+//! See <https://docs.aws.amazon.com/AmazonCloudWatch/latest/monitoring/CloudWatch_Embedded_Metric_Format_Specification.html>.
+
+use std::io::{self, Write};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use http_types::StatusCode;
+use lambda_http::request::RequestContext;
+use serde_json::{json, Map, Value};
+
+/// What was observed about a single invocation.
+#[derive(Debug, Default)]
+pub(crate) struct InvocationMetrics {
+    pub(crate) cold_start: bool,
+    pub(crate) route: Option<String>,
+    pub(crate) duration: Option<Duration>,
+    pub(crate) status: Option<StatusCode>,
+    pub(crate) response_size: Option<usize>,
+    pub(crate) error: bool,
+    pub(crate) batch_item_failures: Option<usize>,
+}
+
+impl InvocationMetrics {
+    pub(crate) fn new(cold_start: bool) -> Self {
+        Self {
+            cold_start,
+            ..Self::default()
+        }
+    }
+
+    /// The route template of the event, such as `GET /items/{id}`, which keeps the `Route` dimension low cardinality.
+    ///
+    /// ALB events have no route template, so are only recorded against the function.
+    pub(crate) fn set_route(&mut self, request_context: Option<&RequestContext>) {
+        self.route = match request_context {
+            Some(RequestContext::ApiGatewayV2(ctx)) => Some(ctx.route_key.clone()),
+            Some(RequestContext::ApiGateway(ctx)) => {
+                Some(format!("{} {}", ctx.http_method, ctx.resource_path))
+            }
+            Some(RequestContext::Alb(_)) | None => None,
+        };
+    }
+
+    /// Build the EMF record for this invocation.
+    pub(crate) fn to_emf(&self, namespace: &str, function_name: &str, request_id: &str) -> Value {
+        let mut dimensions = vec![json!(["FunctionName"])];
+        let mut metrics = vec![
+            json!({ "Name": "Errors", "Unit": "Count" }),
+            json!({ "Name": "ColdStart", "Unit": "Count" }),
+        ];
+        let mut record = Map::new();
+        record.insert("FunctionName".to_owned(), json!(function_name));
+        record.insert("RequestId".to_owned(), json!(request_id));
+        record.insert("Errors".to_owned(), json!(u8::from(self.error)));
+        record.insert("ColdStart".to_owned(), json!(u8::from(self.cold_start)));
+
+        if let Some(route) = &self.route {
+            dimensions.push(json!(["FunctionName", "Route"]));
+            record.insert("Route".to_owned(), json!(route));
+        }
+        if let Some(duration) = self.duration {
+            metrics.push(json!({ "Name": "Duration", "Unit": "Milliseconds" }));
+            record.insert(
+                "Duration".to_owned(),
+                json!(duration.as_secs_f64() * 1000.0),
+            );
+        }
+        if let Some(status) = self.status {
+            // One count per status class, such as `Status5xx`, so each class can be summed and alarmed on.
+            let class = format!("Status{}xx", u16::from(status) / 100);
+            metrics.push(json!({ "Name": class, "Unit": "Count" }));
+            record.insert(class, json!(1));
+            record.insert("StatusCode".to_owned(), json!(u16::from(status)));
+        }
+        if let Some(response_size) = self.response_size {
+            metrics.push(json!({ "Name": "ResponseSize", "Unit": "Bytes" }));
+            record.insert("ResponseSize".to_owned(), json!(response_size));
+        }
+        if let Some(batch_item_failures) = self.batch_item_failures {
+            metrics.push(json!({ "Name": "BatchItemFailures", "Unit": "Count" }));
+            record.insert("BatchItemFailures".to_owned(), json!(batch_item_failures));
+        }
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or(Duration::ZERO)
+            .as_millis() as u64;
+        record.insert(
+            "_aws".to_owned(),
+            json!({
+                "Timestamp": timestamp,
+                "CloudWatchMetrics": [{
+                    "Namespace": namespace,
+                    "Dimensions": dimensions,
+                    "Metrics": metrics,
+                }],
+            }),
+        );
+
+        Value::Object(record)
+    }
+}
+
+/// Write the EMF record to stdout, where Lambda forwards it to CloudWatch Logs and extracts the metrics.
+///
+/// A closed or broken stdout only loses the record, rather than panicking like `println!`.
+pub(crate) fn emit(record: &Value) {
+    let _ = writeln!(io::stdout().lock(), "{}", record);
+}
@@ -0,0 +1,144 @@
+//! Bridging between AWS X-Ray trace headers and OpenTelemetry / W3C Trace Context.
+//!
+//! Requires the `opentelemetry` cargo feature.
+//!
+//! With the feature enabled, the [`LambdaListener`](crate::LambdaListener) starts an OpenTelemetry `invocation` span
+//! for each invocation, using the globally installed tracer provider. The span is a child of the invocation's X-Ray trace,
+//! is current while the Tide server responds, and is propagated to handlers via a W3C `traceparent` request header,
+//! replacing any `traceparent` sent by the client.
+
+use opentelemetry::trace::{
+    SpanContext, SpanId, SpanKind, Status, TraceContextExt, TraceFlags, TraceId, TraceState, Tracer,
+};
+use opentelemetry::{global, Context, KeyValue};
+
+/// The W3C Trace Context header.
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+
+/// Parse an X-Ray trace header, such as `Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=1`,
+/// into a remote OpenTelemetry `SpanContext`.
+///
+/// Returns `None` if the header has no valid `Root` and `Parent`.
+///
+/// ### Example
+/// ```
+/// use tide_lambda_listener::otel::{span_context_from_xray, traceparent};
+///
+/// let span_context = span_context_from_xray(
+///     "Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=1",
+/// )
+/// .unwrap();
+///
+/// assert_eq!(
+///     traceparent(&span_context),
+///     "00-5759e988bd862e3fe1be46a994272793-53995c3f42cd8ad8-01"
+/// );
+/// ```
+pub fn span_context_from_xray(header: &str) -> Option<SpanContext> {
+    let mut trace_id = None;
+    let mut span_id = None;
+    let mut trace_flags = TraceFlags::default();
+
+    for field in header.split(';') {
+        match field.trim().split_once('=') {
+            Some(("Root", root)) => {
+                // `1-{8 hex digit epoch}-{24 hex digit unique id}`
+                let mut parts = root.splitn(3, '-');
+                if parts.next() != Some("1") {
+                    return None;
+                }
+                let hex: String = parts.collect();
+                if hex.len() != 32 {
+                    return None;
+                }
+                trace_id = TraceId::from_hex(&hex).ok();
+            }
+            Some(("Parent", parent)) if parent.len() == 16 => {
+                span_id = SpanId::from_hex(parent).ok();
+            }
+            Some(("Sampled", "1")) => trace_flags = TraceFlags::SAMPLED,
+            _ => {}
+        }
+    }
+
+    let span_context = SpanContext::new(
+        trace_id?,
+        span_id?,
+        trace_flags,
+        true,
+        TraceState::default(),
+    );
+
+    if span_context.is_valid() {
+        Some(span_context)
+    } else {
+        None
+    }
+}
+
+/// Format a `SpanContext` as a W3C `traceparent` header value.
+pub fn traceparent(span_context: &SpanContext) -> String {
+    format!(
+        "00-{}-{}-{:02x}",
+        span_context.trace_id(),
+        span_context.span_id(),
+        span_context.trace_flags().to_u8()
+    )
+}
+
+/// Start the `invocation` span, and inject its `traceparent` into the Tide request.
+pub(crate) fn start_invocation(
+    mut req: http_types::Request,
+    xray_trace_id: &str,
+    request_id: &str,
+) -> (http_types::Request, Context) {
+    let parent = match span_context_from_xray(xray_trace_id) {
+        Some(span_context) => Context::new().with_remote_span_context(span_context),
+        None => Context::new(),
+    };
+
+    let tracer = global::tracer("tide-lambda-listener");
+    let builder = tracer
+        .span_builder("invocation")
+        .with_kind(SpanKind::Server)
+        .with_attributes(vec![KeyValue::new("faas.execution", request_id.to_owned())]);
+    let span = tracer.build_with_context(builder, &parent);
+    let cx = parent.with_span(span);
+
+    let span_context = cx.span().span_context().clone();
+    if span_context.is_valid() {
+        req.insert_header(TRACEPARENT_HEADER, traceparent(&span_context));
+    }
+
+    (req, cx)
+}
+
+/// End the `invocation` span with the response status.
+pub(crate) fn end_invocation(cx: &Context, status: http_types::StatusCode) {
+    let span = cx.span();
+    span.set_attribute(KeyValue::new(
+        "http.status_code",
+        i64::from(u16::from(status)),
+    ));
+    if status.is_server_error() {
+        span.set_status(Status::error(status.canonical_reason()));
+    }
+    span.end();
+}
+
+/// End the `invocation` span as failed, because the handler returned an error or missed the deadline.
+pub(crate) fn fail_invocation(
+    cx: &Context,
+    status: Option<http_types::StatusCode>,
+    message: String,
+) {
+    let span = cx.span();
+    if let Some(status) = status {
+        span.set_attribute(KeyValue::new(
+            "http.status_code",
+            i64::from(u16::from(status)),
+        ));
+    }
+    span.set_status(Status::error(message));
+    span.end();
+}
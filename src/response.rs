@@ -0,0 +1,198 @@
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use async_std::io::{self, ReadExt};
+use http_types::headers::{CONTENT_ENCODING, SET_COOKIE};
+use http_types::{Body, Response};
+use lambda_http::request::RequestContext;
+
+/// Convert a Tide response body into a Lambda response body.
+///
+/// Binary bodies are base64 encoded by Lambda, and are signaled via `isBase64Encoded`.
+/// A body is sent as binary if it has a `Content-Encoding`, if its media type is one of `binary_media_types`,
+/// if its media type is not textual, or if it turns out not to be valid UTF-8.
+pub(crate) async fn lambda_body(
+    res: &Response,
+    body: Body,
+    binary_media_types: &[String],
+) -> http_types::Result<lambda_http::Body> {
+    if body.is_empty() == Some(true) {
+        return Ok(lambda_http::Body::Empty);
+    }
+
+    let bytes = body.into_bytes().await?;
+
+    if is_binary(res, binary_media_types) {
+        return Ok(lambda_http::Body::Binary(bytes));
+    }
+
+    match String::from_utf8(bytes) {
+        Ok(text) => Ok(lambda_http::Body::Text(text)),
+        Err(err) => Ok(lambda_http::Body::Binary(err.into_bytes())),
+    }
+}
+
+fn is_binary(res: &Response, binary_media_types: &[String]) -> bool {
+    let encoded = match res.header(CONTENT_ENCODING) {
+        Some(encoding) => !encoding.as_str().eq_ignore_ascii_case("identity"),
+        None => false,
+    };
+    if encoded {
+        return true;
+    }
+
+    let mime = match res.content_type() {
+        Some(mime) => mime,
+        None => return false,
+    };
+    let essence = mime.essence().to_ascii_lowercase();
+
+    binary_media_types
+        .iter()
+        .any(|media_type| media_type_matches(media_type, &essence))
+        || !is_text_media_type(&essence)
+}
+
+/// Match a media type essence against a pattern such as `image/png`, `image/*`, or `*/*`.
+fn media_type_matches(pattern: &str, essence: &str) -> bool {
+    let pattern = pattern.trim().to_ascii_lowercase();
+
+    if pattern == "*/*" || pattern == essence {
+        return true;
+    }
+
+    match pattern.strip_suffix("/*") {
+        Some(basetype) => essence.split('/').next() == Some(basetype),
+        None => false,
+    }
+}
+
+fn is_text_media_type(essence: &str) -> bool {
+    essence.starts_with("text/")
+        || essence.ends_with("+json")
+        || essence.ends_with("+xml")
+        || matches!(
+            essence,
+            "application/json"
+                | "application/javascript"
+                | "application/ecmascript"
+                | "application/xml"
+                | "application/x-www-form-urlencoded"
+                | "application/graphql"
+        )
+}
+
+/// A Lambda response body, ready to be posted to `/runtime/invocation/{id}/response`.
+#[derive(Debug)]
+pub(crate) enum LambdaResponseBody {
+    /// A fully buffered, JSON encoded, Lambda response.
+    Buffered(Body),
+    /// A streamed HTTP integration response.
+    Streaming(Body, StreamFailure),
+}
+
+/// The content type for streamed responses to Lambda Function URLs.
+pub(crate) const HTTP_INTEGRATION_RESPONSE: &str =
+    "application/vnd.awslambda.http-integration-response";
+
+/// Separates the JSON prelude from the body of a streamed response.
+const PRELUDE_DELIMITER: [u8; 8] = [0; 8];
+
+/// Whether a request came from a Lambda Function URL, the only origin which accepts streamed responses.
+///
+/// Function URL events share the HTTP API payload format, so they are told apart by their `*.lambda-url.*` domain.
+pub(crate) fn is_function_url(request_context: Option<&RequestContext>) -> bool {
+    match request_context {
+        Some(RequestContext::ApiGatewayV2(ctx)) => ctx.domain_name.contains(".lambda-url."),
+        _ => false,
+    }
+}
+
+/// Convert a Tide response into a streamed Lambda HTTP integration response.
+///
+/// The response head is sent as a JSON prelude, followed by the Tide body as it is produced.
+/// Since the response head has already been sent by then, failures while reading the body
+/// are recorded in the returned `StreamFailure`, rather than being reported to the Runtime API.
+pub(crate) fn streaming_body(mut res: Response) -> http_types::Result<(Body, StreamFailure)> {
+    let mut headers = serde_json::Map::new();
+    let mut cookies = Vec::new();
+    for (name, values) in res.iter() {
+        if *name == SET_COOKIE {
+            cookies.extend(values.iter().map(|value| value.as_str().to_owned()));
+        } else {
+            let values: Vec<&str> = values.iter().map(|value| value.as_str()).collect();
+            headers.insert(name.as_str().to_owned(), values.join(", ").into());
+        }
+    }
+
+    let prelude = serde_json::json!({
+        "statusCode": u16::from(res.status()),
+        "headers": headers,
+        "cookies": cookies,
+    });
+    let mut head = serde_json::to_vec(&prelude)?;
+    head.extend_from_slice(&PRELUDE_DELIMITER);
+
+    let failure = StreamFailure::default();
+    let body = TapErrors {
+        inner: io::Cursor::new(head).chain(res.take_body()),
+        failure: failure.clone(),
+    };
+
+    Ok((Body::from_reader(io::BufReader::new(body), None), failure))
+}
+
+/// Records a failure which happened while streaming a response body.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct StreamFailure(Arc<Mutex<Option<String>>>);
+
+impl StreamFailure {
+    pub(crate) fn take(&self) -> Option<String> {
+        self.0.lock().expect("StreamFailure lock poisoned").take()
+    }
+
+    fn set(&self, err: &io::Error) {
+        *self.0.lock().expect("StreamFailure lock poisoned") = Some(err.to_string());
+    }
+}
+
+#[derive(Debug)]
+struct TapErrors<R> {
+    inner: R,
+    failure: StreamFailure,
+}
+
+impl<R: io::Read + Unpin> io::Read for TapErrors<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if let Poll::Ready(Err(err)) = &poll {
+            self.failure.set(err);
+        }
+        poll
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn media_type_matches_exact_types() {
+        assert!(media_type_matches("image/png", "image/png"));
+        assert!(media_type_matches(" Image/PNG ", "image/png"));
+        assert!(!media_type_matches("image/png", "image/jpeg"));
+    }
+
+    #[test]
+    fn media_type_matches_wildcards() {
+        assert!(media_type_matches("*/*", "application/json"));
+        assert!(media_type_matches("image/*", "image/webp"));
+        assert!(!media_type_matches("image/*", "application/json"));
+        assert!(!media_type_matches("image/*", "imagery/png"));
+    }
+}
@@ -0,0 +1,72 @@
+use async_std::channel::{self, Receiver, Sender};
+
+/// A handle which asks a [`LambdaListener`](crate::LambdaListener) to shut down.
+///
+/// The listener stops polling for invocations, runs its [shutdown hooks](crate::LambdaListener::with_shutdown),
+/// and returns from `listen`. An invocation which is already being handled is completed first.
+///
+/// This is useful for shutting down from elsewhere in the application, such as a background task
+/// which has detected that the execution environment is going away, or from tests.
+#[derive(Debug, Clone)]
+pub struct ShutdownHandle {
+    sender: Sender<()>,
+}
+
+impl ShutdownHandle {
+    /// Ask the listener to shut down.
+    pub fn shutdown(&self) {
+        // Only the first request matters, so a full channel is fine.
+        let _ = self.sender.try_send(());
+    }
+}
+
+/// The listener's side of its [`ShutdownHandle`]s.
+#[derive(Debug)]
+pub(crate) struct Shutdown {
+    sender: Sender<()>,
+    receiver: Receiver<()>,
+}
+
+impl Shutdown {
+    pub(crate) fn new() -> Self {
+        let (sender, receiver) = channel::bounded(1);
+        Self { sender, receiver }
+    }
+
+    pub(crate) fn handle(&self) -> ShutdownHandle {
+        ShutdownHandle {
+            sender: self.sender.clone(),
+        }
+    }
+
+    /// Wait until shutdown is requested.
+    pub(crate) async fn requested(&self) {
+        // The listener holds a sender itself, so this cannot fail.
+        let _ = self.receiver.recv().await;
+    }
+}
+
+/// Request shutdown when the process receives `SIGTERM`, which Lambda sends before stopping the execution environment.
+#[cfg(unix)]
+pub(crate) fn on_sigterm(handle: ShutdownHandle) {
+    use async_signal::{Signal, Signals};
+    use futures_lite::StreamExt;
+    use tracing::error;
+
+    let mut signals = match Signals::new([Signal::Term]) {
+        Ok(signals) => signals,
+        Err(err) => {
+            error!("Failed to listen for SIGTERM: {}", err);
+            return;
+        }
+    };
+
+    async_std::task::spawn(async move {
+        if signals.next().await.is_some() {
+            handle.shutdown();
+        }
+    });
+}
+
+#[cfg(not(unix))]
+pub(crate) fn on_sigterm(_handle: ShutdownHandle) {}
@@ -0,0 +1,265 @@
+use http_types::{Body, Method, Request};
+use serde::Deserialize;
+use serde_json::{json, Map, Number, Value};
+use tide::Server;
+
+use super::{dispatch_in_order, route, Invocation};
+use crate::error::Error;
+
+/// A DynamoDB Streams record, dispatched as a request to the DynamoDB route.
+///
+/// Keys and images are converted from DynamoDB attribute values to plain JSON, such as `{"id": "abc", "count": 3}`.
+/// Numbers which a JSON number cannot hold exactly, such as those with more than 17 significant digits, are strings.
+/// The request body is a JSON object of the record's `keys`, and its `newImage` and `oldImage` if the stream
+/// includes them.
+///
+/// Available to handlers via [`LambdaRequestExt::dynamodb_record`](crate::LambdaRequestExt::dynamodb_record).
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct DynamoDbRecord {
+    /// The event id.
+    pub event_id: String,
+    /// `INSERT`, `MODIFY`, or `REMOVE`.
+    pub event_name: String,
+    /// The sequence number of the record within its shard, which is reported back in `batchItemFailures` if the
+    /// request fails.
+    pub sequence_number: String,
+    /// The key attributes of the item.
+    pub keys: Value,
+    /// The item after it was modified, if the stream includes new images.
+    pub new_image: Option<Value>,
+    /// The item before it was modified, if the stream includes old images.
+    pub old_image: Option<Value>,
+    /// When the change was made, in seconds since the Unix epoch.
+    pub approximate_creation_date_time: Option<f64>,
+    /// The ARN of the stream.
+    pub event_source_arn: String,
+    /// The region of the table.
+    pub aws_region: String,
+}
+
+impl DynamoDbRecord {
+    /// The name of the table, from the stream's ARN.
+    pub fn table_name(&self) -> &str {
+        self.event_source_arn.split('/').nth(1).unwrap_or_default()
+    }
+}
+
+/// A DynamoDB Streams record as delivered in the event.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawRecord {
+    #[serde(rename = "eventID")]
+    event_id: String,
+    event_name: String,
+    #[serde(rename = "eventSourceARN")]
+    event_source_arn: String,
+    #[serde(default)]
+    aws_region: String,
+    dynamodb: RawChange,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct RawChange {
+    sequence_number: String,
+    #[serde(default)]
+    keys: Map<String, Value>,
+    #[serde(default)]
+    new_image: Option<Map<String, Value>>,
+    #[serde(default)]
+    old_image: Option<Map<String, Value>>,
+    #[serde(default)]
+    approximate_creation_date_time: Option<f64>,
+}
+
+/// Send each record as a `POST` to its table's route in order, stopping at the first failure.
+pub(crate) async fn dispatch<State: Clone + Send + Sync + 'static>(
+    server: &Server<State>,
+    template: &str,
+    records: &[Value],
+    invocation: &Invocation<'_>,
+) -> Result<Value, Error> {
+    let mut requests = Vec::with_capacity(records.len());
+    for record in records {
+        // Without a sequence number, there is nowhere to checkpoint to.
+        let sequence_number = record
+            .pointer("/dynamodb/SequenceNumber")
+            .and_then(Value::as_str)
+            .ok_or_else(|| {
+                Error::invocation(
+                    "InvalidEventDataError",
+                    "DynamoDB Streams record without a `SequenceNumber`",
+                )
+            })?;
+        requests.push((
+            sequence_number.to_owned(),
+            request(template, record, invocation),
+        ));
+    }
+
+    Ok(dispatch_in_order(server, invocation, requests).await)
+}
+
+/// The request for a record, with its keys and images as the JSON body.
+fn request(template: &str, record: &Value, invocation: &Invocation<'_>) -> Result<Request, Error> {
+    let raw = RawRecord::deserialize(record)
+        .map_err(|err| Error::invocation("InvalidEventDataError", err))?;
+    let record = DynamoDbRecord {
+        event_id: raw.event_id,
+        event_name: raw.event_name,
+        sequence_number: raw.dynamodb.sequence_number,
+        keys: from_attribute_map(raw.dynamodb.keys),
+        new_image: raw.dynamodb.new_image.map(from_attribute_map),
+        old_image: raw.dynamodb.old_image.map(from_attribute_map),
+        approximate_creation_date_time: raw.dynamodb.approximate_creation_date_time,
+        event_source_arn: raw.event_source_arn,
+        aws_region: raw.aws_region,
+    };
+
+    let mut req = invocation.request(
+        Method::Post,
+        &route(
+            template,
+            &[
+                ("table", record.table_name()),
+                ("event-name", &record.event_name),
+            ],
+        ),
+    )?;
+    let mut body = json!({ "keys": record.keys });
+    if let Some(new_image) = &record.new_image {
+        body["newImage"] = new_image.clone();
+    }
+    if let Some(old_image) = &record.old_image {
+        body["oldImage"] = old_image.clone();
+    }
+    req.set_body(Body::from_json(&body)?);
+    req.ext_mut().insert(record);
+    Ok(req)
+}
+
+/// Convert a map of DynamoDB attribute values, such as an item, to a plain JSON object.
+fn from_attribute_map(attributes: Map<String, Value>) -> Value {
+    Value::Object(
+        attributes
+            .into_iter()
+            .map(|(name, value)| (name, from_attribute_value(value)))
+            .collect(),
+    )
+}
+
+/// Convert a DynamoDB attribute value, such as `{"N": "3"}`, to plain JSON.
+///
+/// Binary values stay base64 encoded, and numbers which JSON cannot represent exactly stay strings.
+fn from_attribute_value(value: Value) -> Value {
+    let (data_type, value) = match value {
+        Value::Object(typed) if typed.len() == 1 => typed.into_iter().next().unwrap(),
+        other => return other,
+    };
+
+    match (data_type.as_str(), value) {
+        ("N", Value::String(n)) => number(n),
+        ("NULL", _) => Value::Null,
+        ("M", Value::Object(map)) => from_attribute_map(map),
+        ("L", Value::Array(list)) => list.into_iter().map(from_attribute_value).collect(),
+        ("NS", Value::Array(set)) => set
+            .into_iter()
+            .map(|n| match n {
+                Value::String(n) => number(n),
+                other => other,
+            })
+            .collect(),
+        // `S`, `B`, `BOOL`, `SS`, and `BS` are already plain JSON.
+        (_, value) => value,
+    }
+}
+
+/// A DynamoDB number as a JSON number, or as a string if it would not round-trip exactly.
+///
+/// DynamoDB numbers have up to 38 digits of precision, which JSON numbers, as `i64`, `u64`, or `f64`, often cannot hold.
+fn number(n: String) -> Value {
+    if let Ok(int) = n.parse::<i64>() {
+        return Value::from(int);
+    }
+    if let Ok(uint) = n.parse::<u64>() {
+        return Value::from(uint);
+    }
+    match n.parse::<f64>().ok().and_then(Number::from_f64) {
+        Some(float) if float.to_string() == n => Value::Number(float),
+        _ => Value::String(n),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers() {
+        assert_eq!(from_attribute_value(json!({ "N": "42" })), json!(42));
+        assert_eq!(from_attribute_value(json!({ "N": "-7" })), json!(-7));
+        assert_eq!(
+            from_attribute_value(json!({ "N": "18446744073709551615" })),
+            json!(u64::MAX)
+        );
+        assert_eq!(from_attribute_value(json!({ "N": "1.5" })), json!(1.5));
+    }
+
+    #[test]
+    fn numbers_which_would_lose_precision_stay_strings() {
+        assert_eq!(
+            from_attribute_value(json!({ "N": "123456789012345678901234567890" })),
+            json!("123456789012345678901234567890")
+        );
+        assert_eq!(
+            from_attribute_value(json!({ "N": "0.1000000000000000000001" })),
+            json!("0.1000000000000000000001")
+        );
+    }
+
+    #[test]
+    fn number_sets() {
+        assert_eq!(
+            from_attribute_value(json!({ "NS": ["1", "2.5", "99999999999999999999999"] })),
+            json!([1, 2.5, "99999999999999999999999"])
+        );
+    }
+
+    #[test]
+    fn maps_and_lists() {
+        assert_eq!(
+            from_attribute_value(json!({ "M": {
+                "name": { "S": "widget" },
+                "tags": { "L": [{ "S": "a" }, { "N": "1" }, { "BOOL": true }] },
+                "dimensions": { "M": { "width": { "N": "3" } } },
+            } })),
+            json!({ "name": "widget", "tags": ["a", 1, true], "dimensions": { "width": 3 } })
+        );
+    }
+
+    #[test]
+    fn null_and_binary() {
+        assert_eq!(from_attribute_value(json!({ "NULL": true })), Value::Null);
+        assert_eq!(
+            from_attribute_value(json!({ "B": "aGVsbG8=" })),
+            json!("aGVsbG8=")
+        );
+        assert_eq!(
+            from_attribute_value(json!({ "BS": ["aGVsbG8=", "d29ybGQ="] })),
+            json!(["aGVsbG8=", "d29ybGQ="])
+        );
+        assert_eq!(
+            from_attribute_value(json!({ "SS": ["a", "b"] })),
+            json!(["a", "b"])
+        );
+    }
+
+    #[test]
+    fn items() {
+        let mut item = Map::new();
+        item.insert("id".to_owned(), json!({ "S": "abc" }));
+        item.insert("count".to_owned(), json!({ "N": "3" }));
+        assert_eq!(from_attribute_map(item), json!({ "id": "abc", "count": 3 }));
+    }
+}
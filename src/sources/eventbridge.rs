@@ -0,0 +1,88 @@
+use http_types::{Body, Method};
+use serde::Deserialize;
+use serde_json::Value;
+use tide::Server;
+
+use super::{route, Invocation};
+use crate::error::Error;
+
+/// The envelope of an EventBridge event, dispatched as a request to the EventBridge route.
+///
+/// This includes events from scheduled rules, which have a `source` of `aws.events`, and a `detail_type` of
+/// `Scheduled Event`.
+///
+/// Available to handlers via [`LambdaRequestExt::eventbridge_event`](crate::LambdaRequestExt::eventbridge_event).
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub struct EventBridgeEvent {
+    /// The event id.
+    pub id: String,
+    /// The service or application which emitted the event, such as `aws.events` or `com.example.orders`.
+    pub source: String,
+    /// The type of event, such as `Scheduled Event` or `Order Placed`.
+    pub detail_type: String,
+    /// The account the event was emitted in.
+    pub account: String,
+    /// When the event was emitted, in RFC 3339 format.
+    pub time: String,
+    /// The region the event was emitted in.
+    pub region: String,
+    /// The ARNs of resources involved in the event, such as the ARN of a scheduled rule.
+    #[serde(default)]
+    pub resources: Vec<String>,
+}
+
+/// Whether `event` is an EventBridge event.
+pub(crate) fn is_event(event: &Value) -> bool {
+    event.get("detail-type").is_some_and(Value::is_string)
+        && event.get("source").is_some_and(Value::is_string)
+        && event.get("detail").is_some()
+}
+
+/// Send the event's `detail` as a `POST` to the route for its source and detail type.
+///
+/// The event fails the invocation unless the response is successful, so that Lambda retries it.
+pub(crate) async fn dispatch<State: Clone + Send + Sync + 'static>(
+    server: &Server<State>,
+    template: &str,
+    event: &Value,
+    invocation: &Invocation<'_>,
+) -> Result<Value, Error> {
+    let envelope = EventBridgeEvent::deserialize(event)
+        .map_err(|err| Error::invocation("InvalidEventDataError", err))?;
+
+    let mut req = invocation.request(
+        Method::Post,
+        &route(
+            template,
+            &[
+                ("source", &envelope.source),
+                ("detail-type", &envelope.detail_type),
+            ],
+        ),
+    )?;
+    req.set_body(Body::from_json(&event["detail"])?);
+    req.ext_mut().insert(envelope.clone());
+
+    match invocation.respond(server, req).await {
+        Some(Ok(res)) if res.status().is_success() => Ok(Value::Null),
+        Some(Ok(res)) => Err(Error::invocation(
+            "EventHandlerError",
+            format!(
+                "EventBridge event {} ({}) failed: {}",
+                envelope.id,
+                envelope.detail_type,
+                res.status()
+            ),
+        )),
+        Some(Err(err)) => Err(err.into()),
+        None => Err(Error::invocation(
+            "DeadlineExceededError",
+            format!(
+                "EventBridge event {} ({}) was not handled before the invocation deadline",
+                envelope.id, envelope.detail_type
+            ),
+        )),
+    }
+}
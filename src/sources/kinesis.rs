@@ -0,0 +1,112 @@
+use http_types::{Method, Request};
+use serde::Deserialize;
+use serde_json::Value;
+use tide::Server;
+
+use super::{dispatch_in_order, route, Invocation};
+use crate::error::Error;
+
+/// A Kinesis record, dispatched as a request to the Kinesis route.
+///
+/// The decoded record data is the request body.
+///
+/// Available to handlers via [`LambdaRequestExt::kinesis_record`](crate::LambdaRequestExt::kinesis_record).
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct KinesisRecord {
+    /// The event id, made up of the shard id and sequence number.
+    pub event_id: String,
+    /// The partition key the record was put with.
+    pub partition_key: String,
+    /// The sequence number of the record within its shard, which is reported back in `batchItemFailures` if the
+    /// request fails.
+    pub sequence_number: String,
+    /// When the record was added to the stream, in seconds since the Unix epoch.
+    pub approximate_arrival_timestamp: f64,
+    /// The ARN of the stream.
+    pub event_source_arn: String,
+    /// The region of the stream.
+    pub aws_region: String,
+}
+
+impl KinesisRecord {
+    /// The name of the stream, from its ARN.
+    pub fn stream_name(&self) -> &str {
+        self.event_source_arn.split('/').nth(1).unwrap_or_default()
+    }
+}
+
+/// A Kinesis record as delivered in the event.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawRecord {
+    #[serde(rename = "eventID")]
+    event_id: String,
+    #[serde(rename = "eventSourceARN")]
+    event_source_arn: String,
+    #[serde(default)]
+    aws_region: String,
+    kinesis: RawData,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawData {
+    partition_key: String,
+    sequence_number: String,
+    data: String,
+    #[serde(default)]
+    approximate_arrival_timestamp: f64,
+}
+
+/// Send each record's data as a `POST` to its stream's route in order, stopping at the first failure.
+pub(crate) async fn dispatch<State: Clone + Send + Sync + 'static>(
+    server: &Server<State>,
+    template: &str,
+    records: &[Value],
+    invocation: &Invocation<'_>,
+) -> Result<Value, Error> {
+    let mut requests = Vec::with_capacity(records.len());
+    for record in records {
+        // Without a sequence number, there is nowhere to checkpoint to.
+        let sequence_number = record
+            .pointer("/kinesis/sequenceNumber")
+            .and_then(Value::as_str)
+            .ok_or_else(|| {
+                Error::invocation(
+                    "InvalidEventDataError",
+                    "Kinesis record without a `sequenceNumber`",
+                )
+            })?;
+        requests.push((
+            sequence_number.to_owned(),
+            request(template, record, invocation),
+        ));
+    }
+
+    Ok(dispatch_in_order(server, invocation, requests).await)
+}
+
+/// The request for a record, with its decoded data as the body.
+fn request(template: &str, record: &Value, invocation: &Invocation<'_>) -> Result<Request, Error> {
+    let raw = RawRecord::deserialize(record)
+        .map_err(|err| Error::invocation("InvalidEventDataError", err))?;
+    let data = base64::decode(&raw.kinesis.data)
+        .map_err(|err| Error::invocation("InvalidEventDataError", err))?;
+    let record = KinesisRecord {
+        event_id: raw.event_id,
+        partition_key: raw.kinesis.partition_key,
+        sequence_number: raw.kinesis.sequence_number,
+        approximate_arrival_timestamp: raw.kinesis.approximate_arrival_timestamp,
+        event_source_arn: raw.event_source_arn,
+        aws_region: raw.aws_region,
+    };
+
+    let mut req = invocation.request(
+        Method::Post,
+        &route(template, &[("stream", record.stream_name())]),
+    )?;
+    req.set_body(data);
+    req.ext_mut().insert(record);
+    Ok(req)
+}
@@ -0,0 +1,277 @@
+//! Non-HTTP Lambda event sources, dispatched to Tide routes.
+//!
+//! Each source is opt-in, by configuring the route template its events are sent to on the
+//! [`LambdaListener`](crate::LambdaListener). Events from sources without a route are handled as HTTP events.
+//! Each dispatched request has the invocation context, and a description of the record it was made from,
+//! available via [`LambdaRequestExt`](crate::LambdaRequestExt).
+
+use std::time::{Duration, Instant};
+
+use async_std::future;
+use http_types::{Method, Request, Response, Url};
+use lambda_http::Context;
+use serde_json::{json, Value};
+use tide::Server;
+use tracing::error;
+
+use crate::error::Error;
+use crate::Extensions;
+
+mod dynamodb;
+mod eventbridge;
+mod kinesis;
+mod s3;
+mod sns;
+mod sqs;
+
+pub use dynamodb::DynamoDbRecord;
+pub use eventbridge::EventBridgeEvent;
+pub use kinesis::KinesisRecord;
+pub use s3::S3Notification;
+pub use sns::{SnsMessage, SnsMessageAttribute};
+pub use sqs::{SqsMessage, SqsMessageAttribute};
+
+/// The route templates non-HTTP events are dispatched to.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Routes {
+    pub(crate) sqs: Option<String>,
+    pub(crate) eventbridge: Option<String>,
+    pub(crate) kinesis: Option<String>,
+    pub(crate) dynamodb: Option<String>,
+    pub(crate) s3: Option<String>,
+    pub(crate) sns: Option<String>,
+}
+
+impl Routes {
+    /// Whether no sources are routed, so every event is an HTTP event.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.sqs.is_none()
+            && self.eventbridge.is_none()
+            && self.kinesis.is_none()
+            && self.dynamodb.is_none()
+            && self.s3.is_none()
+            && self.sns.is_none()
+    }
+}
+
+/// Dispatch a non-HTTP event to its configured route, returning the Lambda response,
+/// or `None` if the event is not from a routed source.
+pub(crate) async fn dispatch<State: Clone + Send + Sync + 'static>(
+    server: &Server<State>,
+    routes: &Routes,
+    event: &Value,
+    invocation: &Invocation<'_>,
+) -> Option<Result<Value, Error>> {
+    if let (Some(template), Some(records)) = (&routes.sqs, records(event, "eventSource", "aws:sqs"))
+    {
+        return Some(sqs::dispatch(server, template, records, invocation).await);
+    }
+    if let (Some(template), Some(records)) = (
+        &routes.kinesis,
+        records(event, "eventSource", "aws:kinesis"),
+    ) {
+        return Some(kinesis::dispatch(server, template, records, invocation).await);
+    }
+    if let (Some(template), Some(records)) = (
+        &routes.dynamodb,
+        records(event, "eventSource", "aws:dynamodb"),
+    ) {
+        return Some(dynamodb::dispatch(server, template, records, invocation).await);
+    }
+    if let (Some(template), Some(records)) = (&routes.s3, records(event, "eventSource", "aws:s3")) {
+        return Some(s3::dispatch(server, template, records, invocation).await);
+    }
+    if let (Some(template), Some(records)) = (&routes.sns, records(event, "EventSource", "aws:sns"))
+    {
+        return Some(sns::dispatch(server, template, records, invocation).await);
+    }
+    if let Some(template) = routes
+        .eventbridge
+        .as_deref()
+        .filter(|_| eventbridge::is_event(event))
+    {
+        return Some(eventbridge::dispatch(server, template, event, invocation).await);
+    }
+
+    None
+}
+
+/// What every request dispatched for an invocation carries.
+pub(crate) struct Invocation<'a> {
+    pub(crate) ctx: &'a Context,
+    pub(crate) extensions: &'a Extensions,
+    pub(crate) deadline: Instant,
+}
+
+impl Invocation<'_> {
+    /// A request to `path`, with the invocation context and extensions.
+    fn request(&self, method: Method, path: &str) -> Result<Request, Error> {
+        let url = Url::parse("http://localhost/")
+            .and_then(|base| base.join(path))
+            .map_err(|err| Error::invocation("InvalidRouteError", err))?;
+
+        let mut req = Request::new(method, url);
+        req.ext_mut().insert(self.ctx.clone());
+        self.extensions.insert(&mut req);
+        Ok(req)
+    }
+
+    /// Send `req` to the Tide server, or `None` if the invocation deadline passes first.
+    async fn respond<State: Clone + Send + Sync + 'static>(
+        &self,
+        server: &Server<State>,
+        req: Request,
+    ) -> Option<http_types::Result<Response>> {
+        let budget = self.deadline.saturating_duration_since(Instant::now());
+        if budget == Duration::ZERO {
+            return None;
+        }
+
+        future::timeout(budget, server.respond(req)).await.ok()
+    }
+}
+
+/// The records of a `{"Records": [...]}` event, if the first is from `source`.
+fn records<'a>(event: &'a Value, source_key: &str, source: &str) -> Option<&'a [Value]> {
+    let records = event.get("Records")?.as_array()?;
+    if records.first()?.get(source_key)?.as_str()? == source {
+        Some(records)
+    } else {
+        None
+    }
+}
+
+/// Send requests for stream records in order, stopping at the first which could not be parsed, fails, or misses the
+/// invocation deadline.
+///
+/// The sequence number of that record is reported in `batchItemFailures`, so that Lambda retries from it.
+async fn dispatch_in_order<State: Clone + Send + Sync + 'static>(
+    server: &Server<State>,
+    invocation: &Invocation<'_>,
+    requests: Vec<(String, Result<Request, Error>)>,
+) -> Value {
+    for (sequence_number, req) in requests {
+        let req = match req {
+            Ok(req) => req,
+            Err(err) => {
+                error!("Record {} could not be parsed: {}", sequence_number, err);
+                return batch_item_failures(vec![sequence_number]);
+            }
+        };
+        match invocation.respond(server, req).await {
+            Some(Ok(res)) if res.status().is_success() => continue,
+            Some(Ok(res)) => error!("Record {} failed: {}", sequence_number, res.status()),
+            Some(Err(err)) => error!("Record {} failed: {}", sequence_number, err),
+            None => error!(
+                "Record {} was not handled before the invocation deadline",
+                sequence_number
+            ),
+        }
+        return batch_item_failures(vec![sequence_number]);
+    }
+
+    batch_item_failures(Vec::new())
+}
+
+/// Send every request, failing the invocation if any fail or miss the invocation deadline, so that Lambda retries
+/// the event.
+///
+/// Each request is paired with a description of its item for errors, and `items` describes them all.
+async fn dispatch_all<State: Clone + Send + Sync + 'static>(
+    server: &Server<State>,
+    invocation: &Invocation<'_>,
+    items: &str,
+    requests: Vec<(String, Request)>,
+) -> Result<Value, Error> {
+    let total = requests.len();
+    let mut failed = Vec::new();
+    for (item, req) in requests {
+        match invocation.respond(server, req).await {
+            Some(Ok(res)) if res.status().is_success() => continue,
+            Some(Ok(res)) => error!("{} failed: {}", item, res.status()),
+            Some(Err(err)) => error!("{} failed: {}", item, err),
+            None => error!("{} was not handled before the invocation deadline", item),
+        }
+        failed.push(item);
+    }
+
+    if failed.is_empty() {
+        Ok(Value::Null)
+    } else {
+        Err(Error::invocation(
+            "EventHandlerError",
+            format!(
+                "{} of {} {} failed: {}",
+                failed.len(),
+                total,
+                items,
+                failed.join(", ")
+            ),
+        ))
+    }
+}
+
+/// The partial batch response, which tells Lambda to retry only these items.
+fn batch_item_failures(item_identifiers: Vec<String>) -> Value {
+    let failures: Vec<Value> = item_identifiers
+        .into_iter()
+        .map(|id| json!({ "itemIdentifier": id }))
+        .collect();
+
+    json!({ "batchItemFailures": failures })
+}
+
+/// Fill the `{name}` placeholders of a route template with percent-encoded values.
+fn route(template: &str, params: &[(&str, &str)]) -> String {
+    params
+        .iter()
+        .fold(template.to_owned(), |route, (name, value)| {
+            route.replace(&format!("{{{}}}", name), &percent_encode(value))
+        })
+}
+
+/// Percent-encode everything but unreserved characters, so a value is a single path segment.
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percent_encode_keeps_unreserved_characters() {
+        assert_eq!(
+            percent_encode("Orders-2024_v1.fifo~"),
+            "Orders-2024_v1.fifo~"
+        );
+    }
+
+    #[test]
+    fn percent_encode_escapes_everything_else() {
+        assert_eq!(percent_encode("Scheduled Event"), "Scheduled%20Event");
+        assert_eq!(percent_encode("a/b?c#d%"), "a%2Fb%3Fc%23d%25");
+        assert_eq!(percent_encode("café"), "caf%C3%A9");
+    }
+
+    #[test]
+    fn route_fills_placeholders() {
+        assert_eq!(
+            route(
+                "/events/{source}/{detail-type}",
+                &[("source", "aws.events"), ("detail-type", "Scheduled Event")]
+            ),
+            "/events/aws.events/Scheduled%20Event"
+        );
+        assert_eq!(route("/sqs/static", &[("queue", "orders")]), "/sqs/static");
+    }
+}
@@ -0,0 +1,159 @@
+use http_types::Method;
+use serde::Deserialize;
+use serde_json::Value;
+use tide::Server;
+
+use super::{dispatch_all, route, Invocation};
+use crate::error::Error;
+
+/// An S3 event notification for a single object, dispatched as a request to the S3 route.
+///
+/// Available to handlers via [`LambdaRequestExt::s3_notification`](crate::LambdaRequestExt::s3_notification).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct S3Notification {
+    /// The event name, such as `ObjectCreated:Put` or `ObjectRemoved:Delete`.
+    pub event_name: String,
+    /// When the event occurred, in RFC 3339 format.
+    pub event_time: String,
+    /// The name of the bucket.
+    pub bucket: String,
+    /// The object key, decoded from the URL encoding S3 notifications use.
+    pub key: String,
+    /// The size of the object in bytes, absent for removal events.
+    pub size: Option<u64>,
+    /// The ETag of the object, absent for removal events.
+    pub e_tag: Option<String>,
+    /// The version of the object, if the bucket is versioned.
+    pub version_id: Option<String>,
+    /// The region of the bucket.
+    pub aws_region: String,
+}
+
+/// An S3 event notification record as delivered in the event.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawRecord {
+    event_name: String,
+    #[serde(default)]
+    event_time: String,
+    #[serde(default)]
+    aws_region: String,
+    s3: RawEntity,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawEntity {
+    bucket: RawBucket,
+    object: RawObject,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawBucket {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawObject {
+    key: String,
+    #[serde(default)]
+    size: Option<u64>,
+    #[serde(default)]
+    e_tag: Option<String>,
+    #[serde(default)]
+    version_id: Option<String>,
+}
+
+/// Send a `POST` to its bucket's route for each object.
+///
+/// Every object is sent, and the invocation fails if any of them failed, so that Lambda retries the event.
+pub(crate) async fn dispatch<State: Clone + Send + Sync + 'static>(
+    server: &Server<State>,
+    template: &str,
+    records: &[Value],
+    invocation: &Invocation<'_>,
+) -> Result<Value, Error> {
+    let notifications = records
+        .iter()
+        .map(|record| {
+            let raw = RawRecord::deserialize(record)?;
+            Ok(S3Notification {
+                event_name: raw.event_name,
+                event_time: raw.event_time,
+                bucket: raw.s3.bucket.name,
+                key: decode_key(&raw.s3.object.key),
+                size: raw.s3.object.size,
+                e_tag: raw.s3.object.e_tag,
+                version_id: raw.s3.object.version_id,
+                aws_region: raw.aws_region,
+            })
+        })
+        .collect::<Result<Vec<_>, serde_json::Error>>()
+        .map_err(|err| Error::invocation("InvalidEventDataError", err))?;
+
+    let mut requests = Vec::with_capacity(notifications.len());
+    for notification in notifications {
+        let mut req = invocation.request(
+            Method::Post,
+            &route(template, &[("bucket", &notification.bucket)]),
+        )?;
+        let object = format!("s3://{}/{}", notification.bucket, notification.key);
+        req.ext_mut().insert(notification);
+        requests.push((object, req));
+    }
+
+    dispatch_all(server, invocation, "S3 notifications", requests).await
+}
+
+/// Decode an object key from an S3 notification, which are form URL encoded, with `+` for spaces.
+fn decode_key(key: &str) -> String {
+    let mut decoded = Vec::with_capacity(key.len());
+    let mut bytes = key.bytes();
+    while let Some(byte) = bytes.next() {
+        match byte {
+            b'+' => decoded.push(b' '),
+            b'%' => {
+                let hex = bytes.clone().take(2).collect::<Vec<_>>();
+                match std::str::from_utf8(&hex)
+                    .ok()
+                    .filter(|hex| hex.len() == 2)
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                {
+                    Some(byte) => {
+                        decoded.push(byte);
+                        bytes.nth(1);
+                    }
+                    // Not an escape, so keep it as it is.
+                    None => decoded.push(b'%'),
+                }
+            }
+            byte => decoded.push(byte),
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_key_decodes_plus_as_space() {
+        assert_eq!(decode_key("my+photo.jpg"), "my photo.jpg");
+    }
+
+    #[test]
+    fn decode_key_decodes_percent_escapes() {
+        assert_eq!(decode_key("photo%281%29.jpg"), "photo(1).jpg");
+        assert_eq!(decode_key("a%2Bb"), "a+b");
+        assert_eq!(decode_key("caf%C3%A9"), "café");
+    }
+
+    #[test]
+    fn decode_key_keeps_invalid_escapes() {
+        assert_eq!(decode_key("100%"), "100%");
+        assert_eq!(decode_key("50%zz"), "50%zz");
+        assert_eq!(decode_key("%4"), "%4");
+    }
+}
@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use http_types::headers::{HeaderName, HeaderValue};
+use http_types::Method;
+use serde::Deserialize;
+use serde_json::Value;
+use tide::Server;
+use tracing::warn;
+
+use super::{dispatch_all, route, Invocation};
+use crate::error::Error;
+
+/// An SNS message, dispatched as a request to the SNS route.
+///
+/// Available to handlers via [`LambdaRequestExt::sns_message`](crate::LambdaRequestExt::sns_message).
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
+pub struct SnsMessage {
+    /// The message id.
+    pub message_id: String,
+    /// The ARN of the topic the message was published to.
+    pub topic_arn: String,
+    /// The subject of the message, if it was published with one.
+    #[serde(default)]
+    pub subject: Option<String>,
+    /// The message, which is also the request body.
+    pub message: String,
+    /// When the message was published, in RFC 3339 format.
+    #[serde(default)]
+    pub timestamp: String,
+    /// Message attributes set by the publisher, which are also request headers.
+    #[serde(default)]
+    pub message_attributes: HashMap<String, SnsMessageAttribute>,
+}
+
+impl SnsMessage {
+    /// The name of the topic, from its ARN.
+    pub fn topic_name(&self) -> &str {
+        self.topic_arn.rsplit(':').next().unwrap_or_default()
+    }
+}
+
+/// A message attribute set by the publisher of an SNS message.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
+pub struct SnsMessageAttribute {
+    /// `String`, `String.Array`, `Number`, or `Binary`.
+    #[serde(rename = "Type")]
+    pub data_type: String,
+    /// The value, which is JSON for `String.Array`, and base64 encoded for `Binary`.
+    pub value: String,
+}
+
+/// An SNS record as delivered in the event.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct RawRecord {
+    sns: SnsMessage,
+}
+
+/// Send each message as a `POST` to its topic's route.
+///
+/// Every message is sent, and the invocation fails if any of them failed, so that SNS retries the event.
+pub(crate) async fn dispatch<State: Clone + Send + Sync + 'static>(
+    server: &Server<State>,
+    template: &str,
+    records: &[Value],
+    invocation: &Invocation<'_>,
+) -> Result<Value, Error> {
+    let messages = records
+        .iter()
+        .map(|record| RawRecord::deserialize(record).map(|raw| raw.sns))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| Error::invocation("InvalidEventDataError", err))?;
+
+    let mut requests = Vec::with_capacity(messages.len());
+    for message in messages {
+        let mut req = invocation.request(
+            Method::Post,
+            &route(template, &[("topic", message.topic_name())]),
+        )?;
+        for (name, attribute) in &message.message_attributes {
+            match (
+                HeaderName::from_str(name),
+                HeaderValue::from_str(&attribute.value),
+            ) {
+                (Ok(name), Ok(value)) => {
+                    req.insert_header(name, value);
+                }
+                _ => warn!(
+                    "SNS message attribute {} of message {} is not a valid header",
+                    name, message.message_id
+                ),
+            }
+        }
+        req.set_body(message.message.as_str());
+        let item = format!("SNS message {}", message.message_id);
+        req.ext_mut().insert(message);
+        requests.push((item, req));
+    }
+
+    dispatch_all(server, invocation, "SNS messages", requests).await
+}
@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+
+use http_types::Method;
+use serde::Deserialize;
+use serde_json::Value;
+use tide::Server;
+use tracing::error;
+
+use super::{batch_item_failures, route, Invocation};
+use crate::error::Error;
+
+/// An SQS message, dispatched as a request to the SQS route.
+///
+/// Available to handlers via [`LambdaRequestExt::sqs_message`](crate::LambdaRequestExt::sqs_message).
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct SqsMessage {
+    /// The message id, which is reported back in `batchItemFailures` if the request fails.
+    pub message_id: String,
+    /// The receipt handle of this delivery of the message.
+    pub receipt_handle: String,
+    /// The message body, which is also the request body.
+    pub body: String,
+    /// System attributes, such as `ApproximateReceiveCount` and `MessageGroupId`.
+    #[serde(default)]
+    pub attributes: HashMap<String, String>,
+    /// Message attributes set by the sender.
+    #[serde(default)]
+    pub message_attributes: HashMap<String, SqsMessageAttribute>,
+    /// The ARN of the queue.
+    #[serde(rename = "eventSourceARN")]
+    pub event_source_arn: String,
+    /// The region of the queue.
+    #[serde(default)]
+    pub aws_region: String,
+}
+
+impl SqsMessage {
+    /// The name of the queue, from its ARN.
+    pub fn queue_name(&self) -> &str {
+        self.event_source_arn.rsplit(':').next().unwrap_or_default()
+    }
+}
+
+/// A message attribute set by the sender of an SQS message.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct SqsMessageAttribute {
+    /// `String`, `Number`, or `Binary`, optionally with a custom suffix.
+    pub data_type: String,
+    /// The value of `String` and `Number` attributes.
+    #[serde(default)]
+    pub string_value: Option<String>,
+    /// The base64 encoded value of `Binary` attributes.
+    #[serde(default)]
+    pub binary_value: Option<String>,
+}
+
+/// Send each message as a `POST` to its queue's route in order, reporting failed messages in `batchItemFailures`.
+///
+/// Messages which cannot be parsed are reported as failed without being sent. Messages left when the invocation
+/// deadline is reached are reported as failed, as are those after the first failure in a batch from a FIFO queue,
+/// so that they are not handled out of order.
+pub(crate) async fn dispatch<State: Clone + Send + Sync + 'static>(
+    server: &Server<State>,
+    template: &str,
+    records: &[Value],
+    invocation: &Invocation<'_>,
+) -> Result<Value, Error> {
+    // Without a message id, a failure cannot be reported for just that message.
+    let message_ids = records
+        .iter()
+        .map(|record| record.get("messageId").and_then(Value::as_str))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| {
+            Error::invocation("InvalidEventDataError", "SQS record without a `messageId`")
+        })?;
+
+    let mut failures = Vec::new();
+    let mut stopped = false;
+    for (record, message_id) in records.iter().zip(message_ids) {
+        if stopped {
+            failures.push(message_id.to_owned());
+            continue;
+        }
+
+        let fifo = record.pointer("/attributes/MessageGroupId").is_some();
+        let message = match SqsMessage::deserialize(record) {
+            Ok(message) => message,
+            Err(err) => {
+                error!("SQS message {} could not be parsed: {}", message_id, err);
+                failures.push(message_id.to_owned());
+                stopped = fifo;
+                continue;
+            }
+        };
+
+        let mut req = invocation.request(
+            Method::Post,
+            &route(template, &[("queue", message.queue_name())]),
+        )?;
+        req.set_body(message.body.as_str());
+        req.ext_mut().insert(message);
+
+        match invocation.respond(server, req).await {
+            Some(Ok(res)) if res.status().is_success() => continue,
+            Some(Ok(res)) => error!("SQS message {} failed: {}", message_id, res.status()),
+            Some(Err(err)) => error!("SQS message {} failed: {}", message_id, err),
+            None => {
+                error!(
+                    "SQS message {} was not handled before the invocation deadline",
+                    message_id
+                );
+                stopped = true;
+            }
+        }
+        failures.push(message_id.to_owned());
+        stopped |= fifo;
+    }
+
+    Ok(batch_item_failures(failures))
+}
@@ -0,0 +1,315 @@
+//! A receiver for the Lambda Telemetry API, which delivers platform events and logs to extensions.
+//!
+//! Requires the `telemetry` cargo feature.
+//!
+//! [`TelemetrySubscription::subscribe`] starts a small Tide app to receive telemetry, and subscribes it via the
+//! Extensions API. Each event is parsed into a [`TelemetryEvent`], and passed to a callback.
+//!
+//! ### Example
+//! ```no_run
+//! use tide_lambda_listener::extension::Extension;
+//! use tide_lambda_listener::telemetry::{TelemetryRecord, TelemetrySubscription};
+//! use tide_lambda_listener::LambdaListener;
+//!
+//! #[async_std::main]
+//! async fn main() -> tide::http::Result<()> {
+//!     let extension = Extension::builder("billing-metrics").register().await?;
+//!     TelemetrySubscription::new()
+//!         .subscribe(&extension, |event| async move {
+//!             if let TelemetryRecord::Report(report) = event.record {
+//!                 println!(
+//!                     "{} billed {}ms, used {}MB",
+//!                     report.request_id, report.metrics.billed_duration_ms, report.metrics.max_memory_used_mb
+//!                 );
+//!             }
+//!             Ok(())
+//!         })
+//!         .await?;
+//!     async_std::task::spawn(extension.run(|_| async { Ok(()) }));
+//!
+//!     let mut server = tide::new();
+//!     server.listen(LambdaListener::new()).await?;
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_std::net::TcpListener;
+use async_std::task;
+use http_types::Body;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tide::{Request, Response, StatusCode};
+use tracing::error;
+
+use crate::extension::Extension;
+use crate::hooks::BoxFuture;
+
+/// The Telemetry API schema version events are parsed from.
+pub const SCHEMA_VERSION: &str = "2022-12-13";
+
+/// The kinds of telemetry a subscription can receive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum TelemetryType {
+    /// Platform events, such as `platform.initStart`, `platform.runtimeDone`, and `platform.report`.
+    Platform,
+    /// Logs written by the function.
+    Function,
+    /// Logs written by extensions.
+    Extension,
+}
+
+/// A telemetry event.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct TelemetryEvent {
+    /// When the event was generated, in ISO 8601 format.
+    pub time: String,
+    /// The event type, such as `platform.report`.
+    pub event_type: String,
+    /// The parsed event.
+    pub record: TelemetryRecord,
+}
+
+/// The body of a [`TelemetryEvent`].
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum TelemetryRecord {
+    /// `platform.initStart`: the init phase has started.
+    InitStart(InitStart),
+    /// `platform.runtimeDone`: the runtime has finished an invocation.
+    RuntimeDone(RuntimeDone),
+    /// `platform.report`: the billing report of an invocation.
+    Report(Report),
+    /// `function`: a log line written by the function, either a string, or an object for JSON logs.
+    Function(Value),
+    /// Any other event, or an event which could not be parsed.
+    Other(Value),
+}
+
+/// The body of a `platform.initStart` event.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct InitStart {
+    /// `on-demand`, `provisioned-concurrency`, or `snap-start`.
+    pub initialization_type: String,
+    /// `init` or `invoke`.
+    pub phase: String,
+}
+
+/// The body of a `platform.runtimeDone` event.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct RuntimeDone {
+    /// The AWS request id of the invocation.
+    pub request_id: String,
+    /// `success`, `failure`, `error`, or `timeout`.
+    pub status: String,
+    /// Metrics for the invocation.
+    #[serde(default)]
+    pub metrics: Option<RuntimeDoneMetrics>,
+}
+
+/// Metrics of a `platform.runtimeDone` event.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct RuntimeDoneMetrics {
+    /// How long the runtime took, in milliseconds.
+    pub duration_ms: f64,
+    /// How many bytes of response were produced.
+    #[serde(default)]
+    pub produced_bytes: Option<u64>,
+}
+
+/// The body of a `platform.report` event.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct Report {
+    /// The AWS request id of the invocation.
+    pub request_id: String,
+    /// `success`, `failure`, `error`, or `timeout`.
+    pub status: String,
+    /// Billing metrics for the invocation.
+    pub metrics: ReportMetrics,
+}
+
+/// Metrics of a `platform.report` event.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct ReportMetrics {
+    /// How long the invocation took, in milliseconds.
+    pub duration_ms: f64,
+    /// The billed duration, in milliseconds.
+    pub billed_duration_ms: u64,
+    /// The configured memory, in MB.
+    #[serde(rename = "memorySizeMB")]
+    pub memory_size_mb: u64,
+    /// The maximum memory used, in MB.
+    #[serde(rename = "maxMemoryUsedMB")]
+    pub max_memory_used_mb: u64,
+    /// For the first invocation of an execution environment, how long the init phase took, in milliseconds.
+    #[serde(default)]
+    pub init_duration_ms: Option<f64>,
+}
+
+/// A telemetry event as delivered by the Telemetry API.
+#[derive(Debug, Deserialize)]
+struct RawEvent {
+    time: String,
+    #[serde(rename = "type")]
+    event_type: String,
+    #[serde(default)]
+    record: Value,
+}
+
+impl From<RawEvent> for TelemetryEvent {
+    fn from(raw: RawEvent) -> Self {
+        fn parse<T: for<'de> Deserialize<'de>>(
+            record: Value,
+            variant: fn(T) -> TelemetryRecord,
+        ) -> TelemetryRecord {
+            match serde_json::from_value(record.clone()) {
+                Ok(parsed) => variant(parsed),
+                Err(_) => TelemetryRecord::Other(record),
+            }
+        }
+
+        let record = match raw.event_type.as_str() {
+            "platform.initStart" => parse(raw.record, TelemetryRecord::InitStart),
+            "platform.runtimeDone" => parse(raw.record, TelemetryRecord::RuntimeDone),
+            "platform.report" => parse(raw.record, TelemetryRecord::Report),
+            "function" => TelemetryRecord::Function(raw.record),
+            _ => TelemetryRecord::Other(raw.record),
+        };
+
+        TelemetryEvent {
+            time: raw.time,
+            event_type: raw.event_type,
+            record,
+        }
+    }
+}
+
+type Callback =
+    Arc<dyn Fn(TelemetryEvent) -> BoxFuture<'static, http_types::Result<()>> + Send + Sync>;
+
+/// A subscription to the Lambda Telemetry API.
+///
+/// By default, `platform` and `function` telemetry is received on an ephemeral port, with Lambda's default buffering.
+#[derive(Debug, Clone)]
+pub struct TelemetrySubscription {
+    types: Vec<TelemetryType>,
+    port: u16,
+    host: String,
+    max_items: u32,
+    max_bytes: u32,
+    buffer_timeout: Duration,
+}
+
+impl TelemetrySubscription {
+    /// Create a new `TelemetrySubscription`.
+    pub fn new() -> Self {
+        Self {
+            types: vec![TelemetryType::Platform, TelemetryType::Function],
+            port: 0,
+            host: "sandbox.localdomain".to_owned(),
+            max_items: 1000,
+            max_bytes: 256 * 1024,
+            buffer_timeout: Duration::from_millis(1000),
+        }
+    }
+
+    /// Receive these kinds of telemetry.
+    pub fn types(mut self, types: &[TelemetryType]) -> Self {
+        self.types = types.to_vec();
+        self
+    }
+
+    /// Receive telemetry on this port, rather than an ephemeral one.
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    /// Ask Lambda to deliver telemetry to this host, rather than `sandbox.localdomain`.
+    pub fn host(mut self, host: impl Into<String>) -> Self {
+        self.host = host.into();
+        self
+    }
+
+    /// Deliver buffered telemetry when this many events, or this many bytes, are buffered, or after this timeout.
+    pub fn buffering(mut self, max_items: u32, max_bytes: u32, timeout: Duration) -> Self {
+        self.max_items = max_items;
+        self.max_bytes = max_bytes;
+        self.buffer_timeout = timeout;
+        self
+    }
+
+    /// Start receiving telemetry, and subscribe `extension` to it.
+    ///
+    /// `callback` is called for each event, in the order they are delivered. Callback failures are only logged.
+    pub async fn subscribe<F, Fut>(
+        self,
+        extension: &Extension,
+        callback: F,
+    ) -> http_types::Result<()>
+    where
+        F: Fn(TelemetryEvent) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = http_types::Result<()>> + Send + 'static,
+    {
+        let callback: Callback = Arc::new(move |event| Box::pin(callback(event)));
+        let mut receiver = tide::with_state(callback);
+        receiver.at("/").post(receive);
+
+        let tcp = TcpListener::bind(("0.0.0.0", self.port)).await?;
+        let port = tcp.local_addr()?.port();
+        task::spawn(receiver.listen(tcp));
+
+        let subscription = json!({
+            "schemaVersion": SCHEMA_VERSION,
+            "types": self.types,
+            "buffering": {
+                "maxItems": self.max_items,
+                "maxBytes": self.max_bytes,
+                "timeoutMs": self.buffer_timeout.as_millis() as u64,
+            },
+            "destination": {
+                "protocol": "HTTP",
+                "URI": format!("http://{}:{}/", self.host, port),
+            },
+        });
+        extension
+            .put("2022-07-01/telemetry", Body::from_json(&subscription)?)
+            .await
+    }
+}
+
+impl Default for TelemetrySubscription {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+async fn receive(mut req: Request<Callback>) -> tide::Result {
+    let events: Vec<RawEvent> = req.body_json().await?;
+    let callback = req.state().clone();
+
+    for event in events {
+        if let Err(err) = callback(event.into()).await {
+            error!("Telemetry callback failed: {}", err);
+        }
+    }
+
+    Ok(Response::new(StatusCode::Ok))
+}
@@ -0,0 +1,294 @@
+//! An in-process stand-in for the Lambda Runtime API, for testing Tide apps behind a [`LambdaListener`].
+//!
+//! Requires the `testing` cargo feature.
+//!
+//! ### Example
+//! ```
+//! use serde_json::json;
+//! use tide_lambda_listener::testing::{MockRuntimeApi, Outcome};
+//!
+//! #[async_std::main]
+//! async fn main() -> tide::http::Result<()> {
+//!     let runtime_api = MockRuntimeApi::start().await?;
+//!
+//!     let mut server = tide::new();
+//!     server.at("/hello").get(|_| async { Ok("Hello, Lambda!") });
+//!
+//!     let listener = runtime_api.listener()?;
+//!     async_std::task::spawn(server.listen(listener));
+//!
+//!     let request_id = runtime_api
+//!         .enqueue(json!({
+//!             "httpMethod": "GET",
+//!             "path": "/hello",
+//!             "headers": { "host": "example.com" },
+//!             "queryStringParameters": null,
+//!             "requestContext": { "elb": { "targetGroupArn": "arn" } },
+//!             "body": null,
+//!             "isBase64Encoded": false
+//!         }))
+//!         .await;
+//!
+//!     match runtime_api.next_outcome().await {
+//!         Outcome::Response { request_id: id, body } => {
+//!             assert_eq!(id, request_id);
+//!             assert_eq!(body["statusCode"], 200);
+//!         }
+//!         outcome => panic!("unexpected outcome: {:?}", outcome),
+//!     }
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use async_std::channel::{self, Receiver, Sender};
+use async_std::io;
+use async_std::net::TcpListener;
+use async_std::task;
+use lambda_runtime::Config;
+use serde_json::Value;
+use tide::{Request, Response, StatusCode};
+
+use crate::{BuildError, LambdaListener};
+
+/// The function timeout used for invocations enqueued with [`MockRuntimeApi::enqueue`].
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The largest buffered response the mock accepts, as with the real Runtime API.
+///
+/// Larger responses are rejected with `413 Payload Too Large`.
+pub const MAX_RESPONSE_SIZE: usize = 6 * 1024 * 1024;
+
+/// A result posted back to the Runtime API by the listener.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum Outcome {
+    /// The listener posted to `/runtime/invocation/{id}/response`.
+    Response {
+        /// The request id of the invocation.
+        request_id: String,
+        /// The posted Lambda response.
+        body: Value,
+    },
+    /// The listener streamed an HTTP integration response to `/runtime/invocation/{id}/response`.
+    StreamedResponse {
+        /// The request id of the invocation.
+        request_id: String,
+        /// The JSON prelude, with the status code, headers, and cookies.
+        prelude: Value,
+        /// The streamed body.
+        body: Vec<u8>,
+    },
+    /// The listener posted to `/runtime/invocation/{id}/error`.
+    Error {
+        /// The request id of the invocation.
+        request_id: String,
+        /// The `errorType` of the posted diagnostic.
+        error_type: String,
+        /// The `errorMessage` of the posted diagnostic.
+        error_message: String,
+    },
+    /// The listener posted to `/runtime/init/error`.
+    InitError {
+        /// The `errorType` of the posted diagnostic.
+        error_type: String,
+        /// The `errorMessage` of the posted diagnostic.
+        error_message: String,
+    },
+}
+
+#[derive(Debug)]
+struct Invocation {
+    request_id: String,
+    deadline: u64,
+    trace_id: String,
+    event: Value,
+}
+
+#[derive(Clone, Debug)]
+struct MockState {
+    invocations: Receiver<Invocation>,
+    outcomes: Sender<Outcome>,
+}
+
+/// A local Runtime API stand-in, listening on a loopback port.
+///
+/// Events are handed out to `invocation/next` in the order they are enqueued,
+/// and everything posted back is collected as an [`Outcome`].
+#[derive(Debug)]
+pub struct MockRuntimeApi {
+    endpoint: String,
+    next_id: AtomicU64,
+    invocations: Sender<Invocation>,
+    outcomes: Receiver<Outcome>,
+}
+
+impl MockRuntimeApi {
+    /// Start a mock Runtime API on a free loopback port.
+    pub async fn start() -> io::Result<Self> {
+        let (invocations_tx, invocations_rx) = channel::unbounded();
+        let (outcomes_tx, outcomes_rx) = channel::unbounded();
+
+        let mut app = tide::with_state(MockState {
+            invocations: invocations_rx,
+            outcomes: outcomes_tx,
+        });
+        app.at("/2018-06-01/runtime/invocation/next").get(next);
+        app.at("/2018-06-01/runtime/invocation/:request_id/response")
+            .post(response);
+        app.at("/2018-06-01/runtime/invocation/:request_id/error")
+            .post(invocation_error);
+        app.at("/2018-06-01/runtime/init/error").post(init_error);
+
+        let tcp = TcpListener::bind(("127.0.0.1", 0)).await?;
+        let endpoint = tcp.local_addr()?.to_string();
+        task::spawn(app.listen(tcp));
+
+        Ok(Self {
+            endpoint,
+            next_id: AtomicU64::new(1),
+            invocations: invocations_tx,
+            outcomes: outcomes_rx,
+        })
+    }
+
+    /// The `host:port` this mock is listening on, as would be found in `AWS_LAMBDA_RUNTIME_API`.
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// A Lambda configuration pointing at this mock.
+    pub fn config(&self) -> Config {
+        Config {
+            endpoint: self.endpoint.clone(),
+            function_name: "test-function".to_owned(),
+            memory: 128,
+            version: "$LATEST".to_owned(),
+            log_stream: "test-log-stream".to_owned(),
+            log_group: "/aws/lambda/test-function".to_owned(),
+        }
+    }
+
+    /// Build a [`LambdaListener`] connected to this mock.
+    pub fn listener<State>(&self) -> Result<LambdaListener<State>, BuildError> {
+        LambdaListener::builder().config(self.config()).build()
+    }
+
+    /// Enqueue an event for the listener, returning its request id.
+    pub async fn enqueue(&self, event: Value) -> String {
+        self.enqueue_with_timeout(event, DEFAULT_TIMEOUT).await
+    }
+
+    /// Enqueue an event for the listener with a specific function timeout, returning its request id.
+    ///
+    /// The invocation deadline is calculated from when the event is enqueued.
+    pub async fn enqueue_with_timeout(&self, event: Value, timeout: Duration) -> String {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let request_id = format!("00000000-0000-0000-0000-{:012}", id);
+        let deadline = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("System time is before the Unix epoch")
+            + timeout;
+
+        self.invocations
+            .send(Invocation {
+                request_id: request_id.clone(),
+                deadline: deadline.as_millis() as u64,
+                trace_id: format!("Root=1-00000000-{:024x};Parent={:016x};Sampled=0", id, id),
+                event,
+            })
+            .await
+            .expect("Mock Runtime API has stopped");
+
+        request_id
+    }
+
+    /// Wait for the next result posted back by the listener.
+    pub async fn next_outcome(&self) -> Outcome {
+        self.outcomes
+            .recv()
+            .await
+            .expect("Mock Runtime API has stopped")
+    }
+}
+
+async fn next(req: Request<MockState>) -> tide::Result {
+    let invocation = req.state().invocations.recv().await?;
+
+    let mut res = Response::new(StatusCode::Ok);
+    res.insert_header("lambda-runtime-aws-request-id", &invocation.request_id);
+    res.insert_header(
+        "lambda-runtime-deadline-ms",
+        invocation.deadline.to_string(),
+    );
+    res.insert_header(
+        "lambda-runtime-invoked-function-arn",
+        "arn:aws:lambda:us-east-1:123456789012:function:test-function",
+    );
+    res.insert_header("lambda-runtime-trace-id", &invocation.trace_id);
+    res.set_body(tide::Body::from_json(&invocation.event)?);
+    Ok(res)
+}
+
+async fn response(mut req: Request<MockState>) -> tide::Result {
+    let request_id = req.param("request_id")?.to_owned();
+
+    let streaming = req
+        .header("lambda-runtime-function-response-mode")
+        .map(|mode| mode.as_str())
+        == Some("streaming");
+    let outcome = if streaming {
+        let bytes = req.body_bytes().await?;
+        let delimiter = bytes
+            .windows(8)
+            .position(|window| window == [0; 8])
+            .ok_or_else(|| {
+                tide::Error::from_str(StatusCode::BadRequest, "Missing prelude delimiter")
+            })?;
+        Outcome::StreamedResponse {
+            request_id,
+            prelude: serde_json::from_slice(&bytes[..delimiter])?,
+            body: bytes[delimiter + 8..].to_vec(),
+        }
+    } else {
+        let bytes = req.body_bytes().await?;
+        if bytes.len() > MAX_RESPONSE_SIZE {
+            let mut res = Response::new(StatusCode::PayloadTooLarge);
+            res.set_body(serde_json::json!({
+                "errorMessage": format!("Exceeded maximum allowed payload size ({} bytes).", MAX_RESPONSE_SIZE),
+                "errorType": "RequestEntityTooLarge",
+            }));
+            return Ok(res);
+        }
+        Outcome::Response {
+            request_id,
+            body: serde_json::from_slice(&bytes)?,
+        }
+    };
+    req.state().outcomes.send(outcome).await?;
+    Ok(Response::new(StatusCode::Accepted))
+}
+
+async fn invocation_error(mut req: Request<MockState>) -> tide::Result {
+    let diagnostic: lambda_runtime::Diagnostic = req.body_json().await?;
+    let outcome = Outcome::Error {
+        request_id: req.param("request_id")?.to_owned(),
+        error_type: diagnostic.error_type,
+        error_message: diagnostic.error_message,
+    };
+    req.state().outcomes.send(outcome).await?;
+    Ok(Response::new(StatusCode::Accepted))
+}
+
+async fn init_error(mut req: Request<MockState>) -> tide::Result {
+    let diagnostic: lambda_runtime::Diagnostic = req.body_json().await?;
+    let outcome = Outcome::InitError {
+        error_type: diagnostic.error_type,
+        error_message: diagnostic.error_message,
+    };
+    req.state().outcomes.send(outcome).await?;
+    Ok(Response::new(StatusCode::Accepted))
+}
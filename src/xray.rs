@@ -0,0 +1,67 @@
+//! AWS X-Ray trace propagation.
+//!
+//! The [`LambdaListener`](crate::LambdaListener) sets `_X_AMZN_TRACE_ID` to the trace header of the current invocation,
+//! which is where AWS SDKs and other libraries look for it. [`XRayTraceHeader`] forwards it on outbound surf requests,
+//! so that downstream calls join the same trace.
+
+use std::env;
+
+use surf::middleware::{Middleware, Next};
+use surf::{Client, Request, Response};
+
+/// The environment variable Lambda runtimes use for the current invocation's trace header.
+pub const TRACE_ID_ENV: &str = "_X_AMZN_TRACE_ID";
+
+/// The HTTP header used to propagate X-Ray trace headers.
+pub const TRACE_ID_HEADER: &str = "X-Amzn-Trace-Id";
+
+/// The trace header of the current invocation, if tracing is enabled.
+pub fn current_trace_id() -> Option<String> {
+    env::var(TRACE_ID_ENV)
+        .ok()
+        .filter(|trace_id| !trace_id.is_empty())
+}
+
+/// Set, or clear, the trace header of the current invocation.
+pub(crate) fn set_current_trace_id(trace_id: &str) {
+    if trace_id.is_empty() {
+        env::remove_var(TRACE_ID_ENV);
+    } else {
+        env::set_var(TRACE_ID_ENV, trace_id);
+    }
+}
+
+/// A surf middleware which adds the current invocation's `X-Amzn-Trace-Id` to outbound requests.
+///
+/// Requests which already have the header are left as-is.
+///
+/// ### Example
+/// ```no_run
+/// use tide_lambda_listener::xray::XRayTraceHeader;
+///
+/// # async fn example() -> surf::Result<()> {
+/// let client = surf::client().with(XRayTraceHeader);
+/// client.get("https://example.com").await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default, Clone, Copy)]
+pub struct XRayTraceHeader;
+
+#[surf::utils::async_trait]
+impl Middleware for XRayTraceHeader {
+    async fn handle(
+        &self,
+        mut req: Request,
+        client: Client,
+        next: Next<'_>,
+    ) -> surf::Result<Response> {
+        if req.header(TRACE_ID_HEADER).is_none() {
+            if let Some(trace_id) = current_trace_id() {
+                req.insert_header(TRACE_ID_HEADER, trace_id);
+            }
+        }
+
+        next.run(req, client).await
+    }
+}
@@ -0,0 +1,405 @@
+#![cfg(feature = "testing")]
+
+use async_std::task;
+use serde_json::{json, Value};
+use tide::{Request, StatusCode};
+use tide_lambda_listener::testing::{MockRuntimeApi, Outcome};
+use tide_lambda_listener::{LambdaListener, LambdaRequestExt};
+
+/// A server which fails requests whose body mentions `fail`, and records each request it is sent.
+async fn start(
+    listener: impl FnOnce(LambdaListener<()>) -> LambdaListener<()>,
+) -> (MockRuntimeApi, async_std::channel::Receiver<String>) {
+    let runtime_api = MockRuntimeApi::start().await.unwrap();
+    let (sender, handled) = async_std::channel::unbounded();
+
+    let mut server = tide::new();
+    server.at("/*").post(move |mut req: Request<()>| {
+        let sender = sender.clone();
+        async move {
+            let body = req.body_string().await?;
+            sender
+                .send(format!("{} {}", req.url().path(), body))
+                .await?;
+            if body.contains("fail") {
+                return Ok(StatusCode::InternalServerError);
+            }
+            Ok(StatusCode::NoContent)
+        }
+    });
+    task::spawn(server.listen(listener(runtime_api.listener().unwrap())));
+
+    (runtime_api, handled)
+}
+
+fn handled(receiver: &async_std::channel::Receiver<String>) -> Vec<String> {
+    std::iter::from_fn(|| receiver.try_recv().ok()).collect()
+}
+
+fn response(outcome: Outcome) -> Value {
+    match outcome {
+        Outcome::Response { body, .. } => body,
+        outcome => panic!("expected a response, got {:?}", outcome),
+    }
+}
+
+fn error(outcome: Outcome) -> (String, String) {
+    match outcome {
+        Outcome::Error {
+            error_type,
+            error_message,
+            ..
+        } => (error_type, error_message),
+        outcome => panic!("expected an error, got {:?}", outcome),
+    }
+}
+
+fn batch_item_failures(ids: &[&str]) -> Value {
+    let failures: Vec<Value> = ids
+        .iter()
+        .map(|id| json!({ "itemIdentifier": id }))
+        .collect();
+    json!({ "batchItemFailures": failures })
+}
+
+fn sqs_message(id: &str, body: &str, attributes: Value) -> Value {
+    json!({
+        "messageId": id,
+        "receiptHandle": "handle",
+        "body": body,
+        "attributes": attributes,
+        "messageAttributes": {},
+        "eventSource": "aws:sqs",
+        "eventSourceARN": "arn:aws:sqs:us-east-1:123456789012:orders.fifo",
+        "awsRegion": "us-east-1"
+    })
+}
+
+#[async_std::test]
+async fn sqs_failures_are_reported_per_message() {
+    let (runtime_api, received) = start(|listener| listener.with_sqs_route("/sqs/{queue}")).await;
+
+    runtime_api
+        .enqueue(json!({ "Records": [
+            sqs_message("1", "ok", json!({})),
+            sqs_message("2", "fail", json!({})),
+            { "messageId": "3", "eventSource": "aws:sqs" },
+            sqs_message("4", "ok", json!({})),
+        ] }))
+        .await;
+
+    assert_eq!(
+        response(runtime_api.next_outcome().await),
+        batch_item_failures(&["2", "3"])
+    );
+    assert_eq!(
+        handled(&received),
+        [
+            "/sqs/orders.fifo ok",
+            "/sqs/orders.fifo fail",
+            "/sqs/orders.fifo ok"
+        ]
+    );
+}
+
+#[async_std::test]
+async fn sqs_fifo_batches_stop_at_the_first_failure() {
+    let (runtime_api, received) = start(|listener| listener.with_sqs_route("/sqs/{queue}")).await;
+
+    let group = json!({ "MessageGroupId": "g1" });
+    runtime_api
+        .enqueue(json!({ "Records": [
+            sqs_message("1", "ok", group.clone()),
+            sqs_message("2", "fail", group.clone()),
+            sqs_message("3", "ok", group),
+        ] }))
+        .await;
+
+    assert_eq!(
+        response(runtime_api.next_outcome().await),
+        batch_item_failures(&["2", "3"])
+    );
+    assert_eq!(
+        handled(&received),
+        ["/sqs/orders.fifo ok", "/sqs/orders.fifo fail"]
+    );
+}
+
+#[async_std::test]
+async fn sqs_messages_are_available_to_handlers() {
+    let runtime_api = MockRuntimeApi::start().await.unwrap();
+    let mut server = tide::new();
+    server
+        .at("/sqs/:queue")
+        .post(|req: Request<()>| async move {
+            let message = req.sqs_message().expect("an SQS message");
+            assert_eq!(message.message_id, "1");
+            assert_eq!(message.queue_name(), "orders.fifo");
+            assert!(req.cold_start().is_some());
+            Ok(StatusCode::NoContent)
+        });
+    let listener = runtime_api
+        .listener()
+        .unwrap()
+        .with_sqs_route("/sqs/{queue}");
+    task::spawn(server.listen(listener));
+
+    runtime_api
+        .enqueue(json!({ "Records": [sqs_message("1", "ok", json!({}))] }))
+        .await;
+    assert_eq!(
+        response(runtime_api.next_outcome().await),
+        batch_item_failures(&[])
+    );
+}
+
+fn eventbridge_event(detail: Value) -> Value {
+    json!({
+        "version": "0",
+        "id": "event-1",
+        "detail-type": "Scheduled Event",
+        "source": "aws.events",
+        "account": "123456789012",
+        "time": "2024-01-01T00:00:00Z",
+        "region": "us-east-1",
+        "resources": ["arn:aws:events:us-east-1:123456789012:rule/nightly"],
+        "detail": detail
+    })
+}
+
+#[async_std::test]
+async fn eventbridge_events_are_sent_to_their_route() {
+    let (runtime_api, received) =
+        start(|listener| listener.with_eventbridge_route("/events/{source}/{detail-type}")).await;
+
+    runtime_api.enqueue(eventbridge_event(json!({}))).await;
+    assert_eq!(response(runtime_api.next_outcome().await), Value::Null);
+    assert_eq!(
+        handled(&received),
+        ["/events/aws.events/Scheduled%20Event {}"]
+    );
+
+    runtime_api.enqueue(eventbridge_event(json!("fail"))).await;
+    let (error_type, error_message) = error(runtime_api.next_outcome().await);
+    assert_eq!(error_type, "EventHandlerError");
+    assert!(error_message.contains("event-1"), "{}", error_message);
+}
+
+fn kinesis_record(sequence_number: &str, data: &str) -> Value {
+    json!({
+        "kinesis": {
+            "kinesisSchemaVersion": "1.0",
+            "partitionKey": "key",
+            "sequenceNumber": sequence_number,
+            "data": data,
+            "approximateArrivalTimestamp": 1_545_084_650.987
+        },
+        "eventSource": "aws:kinesis",
+        "eventVersion": "1.0",
+        "eventID": format!("shardId-000000000006:{}", sequence_number),
+        "eventName": "aws:kinesis:record",
+        "awsRegion": "us-east-1",
+        "eventSourceARN": "arn:aws:kinesis:us-east-1:123456789012:stream/clicks"
+    })
+}
+
+#[async_std::test]
+async fn kinesis_records_stop_at_the_first_failure() {
+    let (runtime_api, received) =
+        start(|listener| listener.with_kinesis_route("/kinesis/{stream}")).await;
+
+    runtime_api
+        .enqueue(json!({ "Records": [
+            kinesis_record("1", &base64::encode("ok")),
+            kinesis_record("2", &base64::encode("fail")),
+            kinesis_record("3", &base64::encode("ok")),
+        ] }))
+        .await;
+
+    assert_eq!(
+        response(runtime_api.next_outcome().await),
+        batch_item_failures(&["2"])
+    );
+    assert_eq!(
+        handled(&received),
+        ["/kinesis/clicks ok", "/kinesis/clicks fail"]
+    );
+}
+
+#[async_std::test]
+async fn kinesis_records_which_cannot_be_decoded_are_checkpointed() {
+    let (runtime_api, received) =
+        start(|listener| listener.with_kinesis_route("/kinesis/{stream}")).await;
+
+    runtime_api
+        .enqueue(json!({ "Records": [
+            kinesis_record("1", &base64::encode("ok")),
+            kinesis_record("2", "!!notbase64"),
+            kinesis_record("3", &base64::encode("ok")),
+        ] }))
+        .await;
+
+    assert_eq!(
+        response(runtime_api.next_outcome().await),
+        batch_item_failures(&["2"])
+    );
+    assert_eq!(handled(&received), ["/kinesis/clicks ok"]);
+}
+
+fn dynamodb_record(sequence_number: &str, new_image: Value) -> Value {
+    json!({
+        "eventID": "1",
+        "eventName": "INSERT",
+        "eventVersion": "1.0",
+        "eventSource": "aws:dynamodb",
+        "awsRegion": "us-east-1",
+        "dynamodb": {
+            "Keys": { "id": { "S": "abc" } },
+            "NewImage": new_image,
+            "SequenceNumber": sequence_number,
+            "SizeBytes": 26,
+            "StreamViewType": "NEW_IMAGE"
+        },
+        "eventSourceARN": "arn:aws:dynamodb:us-east-1:123456789012:table/orders/stream/2024-01-01T00:00:00.000"
+    })
+}
+
+#[async_std::test]
+async fn dynamodb_records_are_sent_as_plain_json() {
+    let (runtime_api, received) =
+        start(|listener| listener.with_dynamodb_route("/dynamodb/{table}/{event-name}")).await;
+
+    runtime_api
+        .enqueue(json!({ "Records": [
+            dynamodb_record("100", json!({ "id": { "S": "abc" }, "total": { "N": "12.5" } })),
+            dynamodb_record("200", json!("not an image")),
+            dynamodb_record("300", json!({ "id": { "S": "def" } })),
+        ] }))
+        .await;
+
+    assert_eq!(
+        response(runtime_api.next_outcome().await),
+        batch_item_failures(&["200"])
+    );
+    assert_eq!(
+        handled(&received),
+        [r#"/dynamodb/orders/INSERT {"keys":{"id":"abc"},"newImage":{"id":"abc","total":12.5}}"#]
+    );
+}
+
+fn s3_record(key: &str) -> Value {
+    json!({
+        "eventVersion": "2.1",
+        "eventSource": "aws:s3",
+        "awsRegion": "us-east-1",
+        "eventTime": "2024-01-01T00:00:00.000Z",
+        "eventName": "ObjectCreated:Put",
+        "s3": {
+            "s3SchemaVersion": "1.0",
+            "bucket": { "name": "uploads", "arn": "arn:aws:s3:::uploads" },
+            "object": { "key": key, "size": 1024, "eTag": "etag", "sequencer": "0A" }
+        }
+    })
+}
+
+#[async_std::test]
+async fn s3_notifications_are_sent_per_object() {
+    let runtime_api = MockRuntimeApi::start().await.unwrap();
+    let mut server = tide::new();
+    server
+        .at("/s3/uploads")
+        .post(|req: Request<()>| async move {
+            let notification = req.s3_notification().expect("an S3 notification");
+            if notification.key.starts_with("fail") {
+                return Ok(StatusCode::InternalServerError);
+            }
+            assert_eq!(notification.key, "my photo (1).jpg");
+            assert_eq!(notification.size, Some(1024));
+            Ok(StatusCode::NoContent)
+        });
+    let listener = runtime_api
+        .listener()
+        .unwrap()
+        .with_s3_route("/s3/{bucket}");
+    task::spawn(server.listen(listener));
+
+    runtime_api
+        .enqueue(json!({ "Records": [s3_record("my+photo+%281%29.jpg")] }))
+        .await;
+    assert_eq!(response(runtime_api.next_outcome().await), Value::Null);
+
+    runtime_api
+        .enqueue(json!({ "Records": [
+            s3_record("my+photo+%281%29.jpg"),
+            s3_record("fail+me.jpg"),
+        ] }))
+        .await;
+    let (error_type, error_message) = error(runtime_api.next_outcome().await);
+    assert_eq!(error_type, "EventHandlerError");
+    assert_eq!(
+        error_message,
+        "1 of 2 S3 notifications failed: s3://uploads/fail me.jpg"
+    );
+}
+
+fn sns_record(message: &str) -> Value {
+    json!({
+        "EventVersion": "1.0",
+        "EventSubscriptionArn": "arn:aws:sns:us-east-1:123456789012:alerts:subscription",
+        "EventSource": "aws:sns",
+        "Sns": {
+            "SignatureVersion": "1",
+            "Timestamp": "2024-01-01T00:00:00.000Z",
+            "Signature": "signature",
+            "SigningCertUrl": "https://example.com",
+            "MessageId": "message-1",
+            "Message": message,
+            "MessageAttributes": {
+                "severity": { "Type": "String", "Value": "high" }
+            },
+            "Type": "Notification",
+            "UnsubscribeUrl": "https://example.com",
+            "TopicArn": "arn:aws:sns:us-east-1:123456789012:alerts",
+            "Subject": "Disk space"
+        }
+    })
+}
+
+#[async_std::test]
+async fn sns_messages_are_sent_with_attribute_headers() {
+    let runtime_api = MockRuntimeApi::start().await.unwrap();
+    let mut server = tide::new();
+    server
+        .at("/sns/alerts")
+        .post(|mut req: Request<()>| async move {
+            let body = req.body_string().await?;
+            assert_eq!(req.header("severity").map(|h| h.as_str()), Some("high"));
+            let message = req.sns_message().expect("an SNS message");
+            assert_eq!(message.subject.as_deref(), Some("Disk space"));
+            assert_eq!(message.topic_name(), "alerts");
+            if body.contains("fail") {
+                return Ok(StatusCode::InternalServerError);
+            }
+            Ok(StatusCode::NoContent)
+        });
+    let listener = runtime_api
+        .listener()
+        .unwrap()
+        .with_sns_route("/sns/{topic}");
+    task::spawn(server.listen(listener));
+
+    runtime_api
+        .enqueue(json!({ "Records": [sns_record("Disk 90% full")] }))
+        .await;
+    assert_eq!(response(runtime_api.next_outcome().await), Value::Null);
+
+    runtime_api
+        .enqueue(json!({ "Records": [sns_record("fail")] }))
+        .await;
+    let (error_type, error_message) = error(runtime_api.next_outcome().await);
+    assert_eq!(error_type, "EventHandlerError");
+    assert_eq!(
+        error_message,
+        "1 of 1 SNS messages failed: SNS message message-1"
+    );
+}
@@ -0,0 +1,107 @@
+#![cfg(feature = "testing")]
+
+use async_std::channel::{self, Receiver};
+use async_std::net::TcpListener;
+use async_std::task;
+use serde_json::{json, Value};
+use tide::{Request, Response, StatusCode};
+use tide_lambda_listener::extension::{EventType, Extension, ExtensionEvent};
+
+/// A minimal Extensions API, which hands out `events` in order, returning its `host:port`.
+async fn extensions_api(events: Vec<Value>) -> String {
+    let (sender, receiver) = channel::unbounded();
+    for event in events {
+        sender.send(event).await.unwrap();
+    }
+
+    let mut app = tide::with_state(receiver);
+    app.at("/2020-01-01/extension/register")
+        .post(|mut req: Request<Receiver<Value>>| async move {
+            let registration: Value = req.body_json().await?;
+            assert_eq!(registration["events"], json!(["INVOKE", "SHUTDOWN"]));
+            assert_eq!(
+                req.header("Lambda-Extension-Name").map(|h| h.as_str()),
+                Some("test-extension")
+            );
+
+            let mut res = Response::new(StatusCode::Ok);
+            res.insert_header("Lambda-Extension-Identifier", "extension-1");
+            res.set_body(json!({
+                "functionName": "test-function",
+                "functionVersion": "$LATEST",
+                "handler": "bootstrap"
+            }));
+            Ok(res)
+        });
+    app.at("/2020-01-01/extension/event/next")
+        .get(|req: Request<Receiver<Value>>| async move {
+            assert_eq!(
+                req.header("Lambda-Extension-Identifier")
+                    .map(|h| h.as_str()),
+                Some("extension-1")
+            );
+            let event = req.state().recv().await?;
+            Ok(Response::builder(StatusCode::Ok).body(event).build())
+        });
+
+    let tcp = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+    let endpoint = tcp.local_addr().unwrap().to_string();
+    task::spawn(app.listen(tcp));
+    endpoint
+}
+
+#[async_std::test]
+async fn extensions_receive_invoke_and_shutdown_events() {
+    let endpoint = extensions_api(vec![
+        json!({
+            "eventType": "INVOKE",
+            "deadlineMs": 1_704_067_230_000u64,
+            "requestId": "request-1",
+            "invokedFunctionArn": "arn:aws:lambda:us-east-1:123456789012:function:test-function",
+            "tracing": { "type": "X-Amzn-Trace-Id", "value": "Root=1-00000000-000000000000000000000001" }
+        }),
+        json!({
+            "eventType": "SHUTDOWN",
+            "deadlineMs": 1_704_067_232_000u64,
+            "shutdownReason": "spindown"
+        }),
+    ])
+    .await;
+
+    let extension = Extension::builder("test-extension")
+        .events(&[EventType::Invoke, EventType::Shutdown])
+        .endpoint(endpoint)
+        .register()
+        .await
+        .unwrap();
+    assert_eq!(extension.id(), "extension-1");
+    assert_eq!(extension.function_name(), "test-function");
+
+    let (sender, received) = channel::unbounded();
+    extension
+        .run(|event| {
+            let sender = sender.clone();
+            async move {
+                sender.send(event).await?;
+                Ok(())
+            }
+        })
+        .await
+        .unwrap();
+
+    match received.recv().await.unwrap() {
+        ExtensionEvent::Invoke(invoke) => {
+            assert_eq!(invoke.request_id, "request-1");
+            assert_eq!(invoke.deadline_ms, 1_704_067_230_000);
+            assert_eq!(
+                invoke.tracing.map(|tracing| tracing.kind),
+                Some("X-Amzn-Trace-Id".to_owned())
+            );
+        }
+        event => panic!("expected an INVOKE event, got {:?}", event),
+    }
+    match received.recv().await.unwrap() {
+        ExtensionEvent::Shutdown(shutdown) => assert_eq!(shutdown.shutdown_reason, "spindown"),
+        event => panic!("expected a SHUTDOWN event, got {:?}", event),
+    }
+}
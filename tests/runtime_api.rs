@@ -0,0 +1,426 @@
+#![cfg(feature = "testing")]
+
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use async_std::task;
+use serde_json::{json, Value};
+use tide_lambda_listener::testing::{MockRuntimeApi, Outcome, MAX_RESPONSE_SIZE};
+use tide_lambda_listener::{LambdaListener, LambdaRequestExt};
+
+fn alb_event(method: &str, path: &str) -> Value {
+    json!({
+        "httpMethod": method,
+        "path": path,
+        "headers": { "host": "example.com" },
+        "queryStringParameters": null,
+        "requestContext": { "elb": { "targetGroupArn": "arn" } },
+        "body": null,
+        "isBase64Encoded": false
+    })
+}
+
+fn response(outcome: Outcome) -> Value {
+    match outcome {
+        Outcome::Response { body, .. } => body,
+        outcome => panic!("expected a response, got {:?}", outcome),
+    }
+}
+
+fn error_type(outcome: Outcome) -> String {
+    match outcome {
+        Outcome::Error { error_type, .. } => error_type,
+        outcome => panic!("expected an error, got {:?}", outcome),
+    }
+}
+
+#[async_std::test]
+async fn invalid_events_are_reported_and_the_listener_carries_on() {
+    let runtime_api = MockRuntimeApi::start().await.unwrap();
+    let mut server = tide::new();
+    server.at("/hello").get(|_| async { Ok("Hello") });
+    task::spawn(server.listen(runtime_api.listener().unwrap()));
+
+    let request_id = runtime_api.enqueue(json!({ "not": "an http event" })).await;
+    match runtime_api.next_outcome().await {
+        Outcome::Error {
+            request_id: id,
+            error_type,
+            ..
+        } => {
+            assert_eq!(id, request_id);
+            assert_eq!(error_type, "InvalidEventDataError");
+        }
+        outcome => panic!("expected an error, got {:?}", outcome),
+    }
+
+    runtime_api.enqueue(alb_event("GET", "/hello")).await;
+    let body = response(runtime_api.next_outcome().await);
+    assert_eq!(body["statusCode"], 200);
+    assert_eq!(body["body"], "Hello");
+}
+
+#[async_std::test]
+async fn handler_errors_are_responses() {
+    let runtime_api = MockRuntimeApi::start().await.unwrap();
+    let mut server = tide::new();
+    server
+        .at("/fail")
+        .get(|_| async { Err::<String, _>(tide::Error::from_str(503, "Unavailable")) });
+    task::spawn(server.listen(runtime_api.listener().unwrap()));
+
+    runtime_api.enqueue(alb_event("GET", "/fail")).await;
+    let body = response(runtime_api.next_outcome().await);
+    assert_eq!(body["statusCode"], 503);
+}
+
+#[async_std::test]
+async fn binary_bodies_are_base64_encoded() {
+    let runtime_api = MockRuntimeApi::start().await.unwrap();
+    let mut server = tide::new();
+    server.at("/image").get(|_| async {
+        Ok(tide::Response::builder(200)
+            .content_type("image/png")
+            .body(vec![0u8, 159, 146, 150])
+            .build())
+    });
+    server.at("/text").get(|_| async { Ok("plain") });
+    task::spawn(server.listen(runtime_api.listener().unwrap()));
+
+    runtime_api.enqueue(alb_event("GET", "/image")).await;
+    let body = response(runtime_api.next_outcome().await);
+    assert_eq!(body["isBase64Encoded"], true);
+    assert_eq!(body["body"], base64::encode([0u8, 159, 146, 150]));
+
+    runtime_api.enqueue(alb_event("GET", "/text")).await;
+    let body = response(runtime_api.next_outcome().await);
+    assert_eq!(body["isBase64Encoded"], false);
+    assert_eq!(body["body"], "plain");
+}
+
+#[async_std::test]
+async fn init_errors_are_reported_and_stop_the_listener() {
+    let runtime_api = MockRuntimeApi::start().await.unwrap();
+    let server = tide::new();
+    let listener = runtime_api
+        .listener()
+        .unwrap()
+        .with_init(|| async { Err(tide::Error::from_str(500, "Missing DATABASE_URL")) });
+
+    let listened = task::spawn(server.listen(listener));
+    match runtime_api.next_outcome().await {
+        Outcome::InitError { error_message, .. } => {
+            assert_eq!(error_message, "Missing DATABASE_URL")
+        }
+        outcome => panic!("expected an init error, got {:?}", outcome),
+    }
+    assert!(listened.await.is_err());
+}
+
+#[async_std::test]
+async fn build_errors_can_be_reported_with_only_the_runtime_api_endpoint() {
+    let runtime_api = MockRuntimeApi::start().await.unwrap();
+    std::env::set_var("AWS_LAMBDA_RUNTIME_API", runtime_api.endpoint());
+    std::env::remove_var("AWS_LAMBDA_FUNCTION_MEMORY_SIZE");
+
+    let err = LambdaListener::<()>::builder().build().unwrap_err();
+    tide_lambda_listener::report_init_error(&err).await.unwrap();
+
+    match runtime_api.next_outcome().await {
+        Outcome::InitError {
+            error_type,
+            error_message,
+        } => {
+            assert_eq!(error_type, "BuildError");
+            assert_eq!(
+                error_message,
+                "Missing `AWS_LAMBDA_FUNCTION_MEMORY_SIZE` environment variable"
+            );
+        }
+        outcome => panic!("expected an init error, got {:?}", outcome),
+    }
+}
+
+/// An HTTP API event, or a Function URL event if `domain_name` is a `*.lambda-url.*` domain.
+fn http_api_event(domain_name: &str, path: &str) -> Value {
+    json!({
+        "version": "2.0",
+        "routeKey": "$default",
+        "rawPath": path,
+        "rawQueryString": "",
+        "headers": { "host": domain_name },
+        "requestContext": {
+            "accountId": "123456789012",
+            "apiId": "abcdefgh",
+            "domainName": domain_name,
+            "domainPrefix": "abcdefgh",
+            "http": {
+                "method": "GET",
+                "path": path,
+                "protocol": "HTTP/1.1",
+                "sourceIp": "127.0.0.1",
+                "userAgent": "test"
+            },
+            "requestId": "id",
+            "routeKey": "$default",
+            "stage": "$default",
+            "time": "01/Jan/2024:00:00:00 +0000",
+            "timeEpoch": 1704067200000u64
+        },
+        "isBase64Encoded": false
+    })
+}
+
+#[async_std::test]
+async fn streamed_responses_start_with_a_prelude() {
+    let runtime_api = MockRuntimeApi::start().await.unwrap();
+    let mut server = tide::new();
+    server.at("/stream").get(|_| async {
+        Ok(tide::Response::builder(201)
+            .header("x-custom", "yes")
+            .header("set-cookie", "session=abc")
+            .body("streamed body")
+            .build())
+    });
+    let listener = runtime_api
+        .listener()
+        .unwrap()
+        .with_response_streaming(true);
+    task::spawn(server.listen(listener));
+
+    runtime_api
+        .enqueue(http_api_event(
+            "abcdefgh.lambda-url.us-east-1.on.aws",
+            "/stream",
+        ))
+        .await;
+    match runtime_api.next_outcome().await {
+        Outcome::StreamedResponse { prelude, body, .. } => {
+            assert_eq!(prelude["statusCode"], 201);
+            assert_eq!(prelude["headers"]["x-custom"], "yes");
+            assert_eq!(prelude["cookies"], json!(["session=abc"]));
+            assert_eq!(body, b"streamed body");
+        }
+        outcome => panic!("expected a streamed response, got {:?}", outcome),
+    }
+}
+
+#[async_std::test]
+async fn only_function_url_responses_are_streamed() {
+    let runtime_api = MockRuntimeApi::start().await.unwrap();
+    let mut server = tide::new();
+    server.at("/stream").get(|_| async { Ok("buffered body") });
+    let listener = runtime_api
+        .listener()
+        .unwrap()
+        .with_response_streaming(true);
+    task::spawn(server.listen(listener));
+
+    runtime_api.enqueue(alb_event("GET", "/stream")).await;
+    let body = response(runtime_api.next_outcome().await);
+    assert_eq!(body["statusCode"], 200);
+    assert_eq!(body["body"], "buffered body");
+
+    runtime_api
+        .enqueue(http_api_event(
+            "abcdefgh.execute-api.us-east-1.amazonaws.com",
+            "/stream",
+        ))
+        .await;
+    let body = response(runtime_api.next_outcome().await);
+    assert_eq!(body["statusCode"], 200);
+    assert_eq!(body["body"], "buffered body");
+}
+
+fn slow_server() -> tide::Server<()> {
+    let mut server = tide::new();
+    server.at("/slow").get(|_| async {
+        task::sleep(Duration::from_secs(5)).await;
+        Ok("Too late")
+    });
+    server
+}
+
+#[async_std::test]
+async fn handlers_are_cancelled_before_the_deadline() {
+    let runtime_api = MockRuntimeApi::start().await.unwrap();
+    task::spawn(slow_server().listen(runtime_api.listener().unwrap()));
+
+    runtime_api
+        .enqueue_with_timeout(alb_event("GET", "/slow"), Duration::from_secs(1))
+        .await;
+    assert_eq!(
+        error_type(runtime_api.next_outcome().await),
+        "DeadlineExceededError"
+    );
+}
+
+/// A response body which never produces any data.
+struct Stalled;
+
+impl async_std::io::Read for Stalled {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        _: &mut [u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Poll::Pending
+    }
+}
+
+#[async_std::test]
+async fn slow_bodies_are_cancelled_before_the_deadline() {
+    let runtime_api = MockRuntimeApi::start().await.unwrap();
+    let mut server = tide::new();
+    server.at("/proxy").get(|_| async {
+        let body = tide::Body::from_reader(async_std::io::BufReader::new(Stalled), None);
+        Ok(tide::Response::builder(200).body(body).build())
+    });
+    task::spawn(server.listen(runtime_api.listener().unwrap()));
+
+    runtime_api
+        .enqueue_with_timeout(alb_event("GET", "/proxy"), Duration::from_secs(1))
+        .await;
+    assert_eq!(
+        error_type(runtime_api.next_outcome().await),
+        "DeadlineExceededError"
+    );
+}
+
+#[async_std::test]
+async fn timeouts_can_be_gateway_timeout_responses() {
+    let runtime_api = MockRuntimeApi::start().await.unwrap();
+    let listener = runtime_api.listener().unwrap().with_timeout_response(true);
+    task::spawn(slow_server().listen(listener));
+
+    runtime_api
+        .enqueue_with_timeout(alb_event("GET", "/slow"), Duration::from_secs(1))
+        .await;
+    let body = response(runtime_api.next_outcome().await);
+    assert_eq!(body["statusCode"], 504);
+}
+
+#[async_std::test]
+async fn responses_the_runtime_api_rejects_are_reported_as_errors() {
+    let runtime_api = MockRuntimeApi::start().await.unwrap();
+    let mut server = tide::new();
+    server
+        .at("/large")
+        .get(|_| async { Ok("x".repeat(MAX_RESPONSE_SIZE + 1)) });
+    server.at("/hello").get(|_| async { Ok("Hello") });
+    task::spawn(server.listen(runtime_api.listener().unwrap()));
+
+    runtime_api.enqueue(alb_event("GET", "/large")).await;
+    match runtime_api.next_outcome().await {
+        Outcome::Error {
+            error_type,
+            error_message,
+            ..
+        } => {
+            assert_eq!(error_type, "Runtime.ResponseTooLarge");
+            assert!(error_message.contains("413"), "{}", error_message);
+        }
+        outcome => panic!("expected an error, got {:?}", outcome),
+    }
+
+    runtime_api.enqueue(alb_event("GET", "/hello")).await;
+    assert_eq!(response(runtime_api.next_outcome().await)["body"], "Hello");
+}
+
+#[async_std::test]
+async fn shutdown_hooks_run_and_listen_returns_on_shutdown() {
+    let runtime_api = MockRuntimeApi::start().await.unwrap();
+    let mut server = tide::new();
+    server.at("/hello").get(|_| async { Ok("Hello") });
+    let (sender, shut_down) = async_std::channel::unbounded();
+    let listener = runtime_api
+        .listener()
+        .unwrap()
+        .with_shutdown(|| async move {
+            sender.send(()).await?;
+            Ok(())
+        });
+    let handle = listener.shutdown_handle();
+    let listening = task::spawn(server.listen(listener));
+
+    runtime_api.enqueue(alb_event("GET", "/hello")).await;
+    assert_eq!(response(runtime_api.next_outcome().await)["body"], "Hello");
+
+    handle.shutdown();
+    async_std::future::timeout(Duration::from_secs(5), listening)
+        .await
+        .expect("listen did not return after shutdown")
+        .unwrap();
+    assert!(shut_down.try_recv().is_ok());
+}
+
+#[async_std::test]
+async fn after_response_tasks_run_before_the_next_invocation() {
+    let runtime_api = MockRuntimeApi::start().await.unwrap();
+    let log = Arc::new(Mutex::new(Vec::new()));
+
+    let mut server = tide::with_state(log.clone());
+    server
+        .at("/work/:id")
+        .get(|req: tide::Request<Arc<Mutex<Vec<String>>>>| async move {
+            let id = req.param("id")?.to_owned();
+            req.state().lock().unwrap().push(format!("handler {}", id));
+
+            let log = req.state().clone();
+            req.after_response().unwrap().push(async move {
+                task::sleep(Duration::from_millis(300)).await;
+                log.lock().unwrap().push(format!("task {}", id));
+                Ok(())
+            });
+            Ok("Accepted")
+        });
+    task::spawn(server.listen(runtime_api.listener().unwrap()));
+
+    runtime_api.enqueue(alb_event("GET", "/work/a")).await;
+    runtime_api.enqueue(alb_event("GET", "/work/b")).await;
+    response(runtime_api.next_outcome().await);
+    log.lock().unwrap().push("response a".to_owned());
+    response(runtime_api.next_outcome().await);
+
+    assert_eq!(
+        log.lock().unwrap()[..4],
+        ["handler a", "response a", "task a", "handler b"]
+    );
+}
+
+#[async_std::test]
+async fn thaw_hooks_run_only_after_the_idle_threshold() {
+    let runtime_api = MockRuntimeApi::start().await.unwrap();
+    let mut server = tide::new();
+    server.at("/hello").get(|_| async { Ok("Hello") });
+    let (sender, thawed) = async_std::channel::unbounded();
+    let listener =
+        runtime_api
+            .listener()
+            .unwrap()
+            .with_thaw(Duration::from_millis(500), move |idle| {
+                let sender = sender.clone();
+                async move {
+                    sender.send(idle).await?;
+                    Ok(())
+                }
+            });
+    task::spawn(server.listen(listener));
+
+    // Enqueued straight away, so the listener is not idle for long.
+    runtime_api.enqueue(alb_event("GET", "/hello")).await;
+    response(runtime_api.next_outcome().await);
+    assert!(thawed.try_recv().is_err());
+
+    task::sleep(Duration::from_millis(700)).await;
+    runtime_api.enqueue(alb_event("GET", "/hello")).await;
+    response(runtime_api.next_outcome().await);
+    let idle = thawed.try_recv().expect("thaw hook did not run");
+    assert!(idle >= Duration::from_millis(500), "{:?}", idle);
+
+    runtime_api.enqueue(alb_event("GET", "/hello")).await;
+    response(runtime_api.next_outcome().await);
+    assert!(thawed.try_recv().is_err());
+}